@@ -0,0 +1,256 @@
+//! The on-disk cache of GET responses for `--cache`, honouring `Cache-Control: max-age` and
+//! revalidating with `ETag`.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, Result};
+use reqwest::header::{self, HeaderMap};
+use serde::{Deserialize, Serialize};
+
+/// Environment variable pointing at a directory to cache responses in instead of the default one.
+pub const CACHE_ENV: &str = "HTTPIE_CACHE_DIR";
+
+/// Headers kept with a cached body; the rest of the response is dropped.
+const KEPT_HEADERS: [header::HeaderName; 5] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_ENCODING,
+    header::ETAG,
+    header::LAST_MODIFIED,
+    header::CACHE_CONTROL,
+];
+
+/// The per-OS cache directory, like `~/.cache/httpie` on Linux, unless `HTTPIE_CACHE_DIR`
+/// says otherwise.
+pub fn default_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(CACHE_ENV) {
+        return Some(PathBuf::from(dir));
+    }
+    let dirs = directories::ProjectDirs::from("", "", "httpie")?;
+    Some(dirs.cache_dir().to_path_buf())
+}
+
+/// A cached response, stored as `<hash>.json` next to its body in `<hash>.body`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Seconds since the epoch when the response was stored or last revalidated.
+    stored: u64,
+    max_age: u64,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl Entry {
+    /// An entry for a response, or `None` when its headers say it mustn't or needn't be kept.
+    /// A response that varies with request headers isn't kept, as the key is only its URL.
+    pub fn new(url: &str, status: u16, headers: &HeaderMap, body: Vec<u8>, now: SystemTime) -> Option<Self> {
+        if headers.contains_key(header::VARY) {
+            return None;
+        }
+        let max_age = max_age(headers)?;
+        let kept: Vec<_> = KEPT_HEADERS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), headers.get(name)?.to_str().ok()?.to_string())))
+            .collect();
+        if max_age == 0 && !kept.iter().any(|(name, _)| name == header::ETAG.as_str()) {
+            return None;
+        }
+        Some(Self { url: url.to_string(), status, headers: kept, stored: secs(now), max_age, body })
+    }
+
+    /// Whether the entry can be used without asking the server.
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        secs(now) < self.stored.saturating_add(self.max_age)
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.iter().find(|(name, _)| name == header::ETAG.as_str()).map(|(_, value)| value.as_str())
+    }
+
+    /// Starts the entry's lifetime over after a `304 Not Modified` with `headers`.
+    pub fn revalidated(mut self, headers: &HeaderMap, now: SystemTime) -> Self {
+        if let Some(max_age) = max_age(headers) {
+            self.max_age = max_age;
+        }
+        self.stored = secs(now);
+        self
+    }
+}
+
+/// The lifetime `Cache-Control` gives a response, `0` for one that must be revalidated, or
+/// `None` for one that mustn't be stored.
+fn max_age(headers: &HeaderMap) -> Option<u64> {
+    let mut max_age = 0;
+    for value in headers.get_all(header::CACHE_CONTROL) {
+        for directive in value.to_str().ok()?.split(',').map(str::trim) {
+            let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => return None,
+                "no-cache" => return Some(0),
+                "max-age" => max_age = value.trim_matches('"').parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+    Some(max_age)
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs()
+}
+
+fn path_of(dir: &Path, url: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{:x}.{}", md5::compute(url), extension))
+}
+
+/// The entry for `url`, or `None` when there's none or it can't be read.
+pub fn load(dir: &Path, url: &str) -> Option<Entry> {
+    let meta = std::fs::read(path_of(dir, url, "json")).ok()?;
+    let entry: Entry = serde_json::from_slice(&meta).ok()?;
+    // Two URLs with the same hash would otherwise share an entry.
+    if entry.url != url {
+        return None;
+    }
+    let body = std::fs::read(path_of(dir, url, "body")).ok()?;
+    Some(Entry { body, ..entry })
+}
+
+pub fn store(dir: &Path, entry: &Entry) -> Result<()> {
+    let write = || -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(path_of(dir, &entry.url, "body"), &entry.body)?;
+        // The metadata goes last, so an entry is never found without its body.
+        std::fs::write(path_of(dir, &entry.url, "json"), serde_json::to_vec(entry)?)
+    };
+    write().map_err(|e| anyhow!("Failed to write cache {}: {}", dir.display(), e))
+}
+
+/// Stores an entry's body as it arrives, in a `.part` file that only becomes the entry's body
+/// on [`Writer::finish`]; one dropped before then, like for a body cut short, leaves nothing.
+pub struct Writer {
+    dir: PathBuf,
+    entry: Entry,
+    file: Option<File>,
+}
+
+impl Writer {
+    pub fn create(dir: &Path, entry: Entry) -> Result<Self> {
+        let create = || -> std::io::Result<File> {
+            std::fs::create_dir_all(dir)?;
+            File::create(path_of(dir, &entry.url, "part"))
+        };
+        let file = create().map_err(|e| anyhow!("Failed to write cache {}: {}", dir.display(), e))?;
+        Ok(Self { dir: dir.to_path_buf(), entry, file: Some(file) })
+    }
+
+    pub fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        let file = self.file.as_mut().expect("written after finishing");
+        file.write_all(chunk).map_err(|e| anyhow!("Failed to write cache {}: {}", self.dir.display(), e))
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        let (dir, url) = (&self.dir, &self.entry.url);
+        // Closing the file first, and the Drop won't remove what's been renamed.
+        drop(self.file.take());
+        let finish = || -> std::io::Result<()> {
+            std::fs::rename(path_of(dir, url, "part"), path_of(dir, url, "body"))?;
+            std::fs::write(path_of(dir, url, "json"), serde_json::to_vec(&self.entry)?)
+        };
+        finish().map_err(|e| anyhow!("Failed to write cache {}: {}", dir.display(), e))
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(path_of(&self.dir, &self.entry.url, "part"));
+        }
+    }
+}
+
+/// Removes every entry, leaving the directory itself.
+pub fn clear(dir: &Path) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(anyhow!("Failed to read cache {}: {}", dir.display(), e)),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if matches!(path.extension().and_then(|e| e.to_str()), Some("json" | "body" | "part")) {
+            std::fs::remove_file(&path).map_err(|e| anyhow!("Failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn max_age_works() {
+        assert_eq!(max_age(&headers(&[("cache-control", "public, max-age=60")])), Some(60));
+        assert_eq!(max_age(&headers(&[("cache-control", "max-age=60, no-cache")])), Some(0));
+        assert_eq!(max_age(&headers(&[("cache-control", "no-store")])), None);
+        assert_eq!(max_age(&headers(&[])), Some(0));
+    }
+
+    #[test]
+    fn entry_works() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let fresh = headers(&[("cache-control", "max-age=60"), ("content-type", "text/plain"), ("x-other", "1")]);
+        let entry = Entry::new("http://a.b/", 200, &fresh, b"ok".to_vec(), now).unwrap();
+        assert_eq!(entry.headers, [("content-type".to_string(), "text/plain".to_string()), ("cache-control".into(), "max-age=60".into())]);
+        assert!(entry.is_fresh(now + Duration::from_secs(59)));
+        assert!(!entry.is_fresh(now + Duration::from_secs(60)));
+
+        assert!(Entry::new("http://a.b/", 200, &headers(&[]), Vec::new(), now).is_none());
+        let tagged = Entry::new("http://a.b/", 200, &headers(&[("etag", "\"v1\"")]), Vec::new(), now).unwrap();
+        assert_eq!(tagged.etag(), Some("\"v1\""));
+        assert!(!tagged.is_fresh(now));
+        let later = now + Duration::from_secs(10);
+        assert!(tagged.revalidated(&headers(&[("cache-control", "max-age=5")]), later).is_fresh(later));
+    }
+
+    #[test]
+    fn store_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-cache-test-{}", std::process::id()));
+        let now = SystemTime::now();
+        let entry = Entry::new("http://a.b/", 200, &headers(&[("cache-control", "max-age=60")]), b"ok".to_vec(), now).unwrap();
+        store(&dir, &entry).unwrap();
+        assert_eq!(load(&dir, "http://a.b/"), Some(entry));
+        assert_eq!(load(&dir, "http://a.b/other"), None);
+        clear(&dir).unwrap();
+        assert_eq!(load(&dir, "http://a.b/"), None);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn writer_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-cache-writer-test-{}", std::process::id()));
+        let now = SystemTime::now();
+        let entry = Entry::new("http://a.b/", 200, &headers(&[("cache-control", "max-age=60")]), Vec::new(), now).unwrap();
+        let mut writer = Writer::create(&dir, entry.clone()).unwrap();
+        writer.write(b"o").unwrap();
+        drop(writer);
+        assert_eq!(load(&dir, "http://a.b/"), None);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let mut writer = Writer::create(&dir, entry.clone()).unwrap();
+        writer.write(b"o").unwrap();
+        writer.write(b"k").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(load(&dir, "http://a.b/").unwrap().body, b"ok");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(Entry::new("http://a.b/", 200, &headers(&[("cache-control", "max-age=60"), ("vary", "accept")]), Vec::new(), now).is_none());
+    }
+}
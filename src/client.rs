@@ -0,0 +1,175 @@
+//! The request/response core without the command line, for embedding: [`build_client`] makes a
+//! client from [`ClientOptions`], and [`execute`] sends a [`RequestSpec`] with it.
+
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use reqwest::header::{self, HeaderMap};
+use reqwest::{Client, ClientBuilder, Method, StatusCode, Version};
+use serde_json::Value;
+use crate::encoding;
+
+/// How a client behaves for every request it sends.
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Sent with every request unless it has its own.
+    pub headers: HeaderMap,
+    pub follow_redirects: bool,
+    /// Limits the whole exchange, body included.
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub http1_only: bool,
+    /// Speak HTTP/2 from the start, for servers known to support it.
+    pub http2_prior_knowledge: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            follow_redirects: true,
+            timeout: None,
+            connect_timeout: None,
+            http1_only: false,
+            http2_prior_knowledge: false,
+        }
+    }
+}
+
+/// A builder with `options` applied, for callers that have more to configure.
+pub(crate) fn builder(options: &ClientOptions) -> ClientBuilder {
+    let mut builder = Client::builder().default_headers(options.headers.clone());
+    builder = builder.redirect(match options.follow_redirects {
+        true => crate::redirect_policy(false),
+        false => reqwest::redirect::Policy::none(),
+    });
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    if options.http1_only {
+        builder = builder.http1_only();
+    } else if options.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+    builder
+}
+
+pub fn build_client(options: &ClientOptions) -> Result<Client> {
+    Ok(builder(options).build()?)
+}
+
+/// A request to send with [`execute`].
+#[derive(Debug, Clone)]
+pub struct RequestSpec {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+impl RequestSpec {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self { method, url: url.into(), headers: HeaderMap::new(), body: None }
+    }
+}
+
+/// A response and its body, decoded from any `Content-Encoding`. [`execute`] always reads the
+/// whole body; the command line leaves `body` empty when it streamed the body to stdout or a
+/// file instead, or didn't read it at all.
+#[derive(Debug, Clone)]
+pub struct ResponseSummary {
+    pub status: StatusCode,
+    pub version: Version,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// From sending the request until the last byte of the body arrived.
+    pub elapsed: Duration,
+}
+
+impl ResponseSummary {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json(&self) -> Result<Value> {
+        serde_json::from_slice(&self.body).map_err(|e| anyhow!("The response body isn't JSON: {}", e))
+    }
+}
+
+/// Sends `spec` and reads the whole response.
+pub async fn execute(client: &Client, spec: RequestSpec) -> Result<ResponseSummary> {
+    let mut builder = client.request(spec.method, &spec.url).headers(spec.headers);
+    if let Some(body) = spec.body {
+        builder = builder.body(body);
+    }
+    let started = Instant::now();
+    let response = builder.send().await?;
+    let (status, version, headers) = (response.status(), response.version(), response.headers().clone());
+    let raw = response.bytes().await?;
+    let elapsed = started.elapsed();
+    let body = decode_body(&headers, raw.to_vec())?;
+    Ok(ResponseSummary { status, version, headers, body, elapsed })
+}
+
+/// Undoes the `Content-Encoding` in `headers`, if any, on a body read as it came.
+pub(crate) fn decode_body(headers: &HeaderMap, raw: Vec<u8>) -> Result<Vec<u8>> {
+    match headers.get(header::CONTENT_ENCODING).map(|encoding| encoding.to_str()) {
+        Some(Ok(encoding)) if !encoding.eq_ignore_ascii_case("identity") => encoding::decode(encoding, &raw),
+        Some(Err(_)) => Err(anyhow!("Invalid Content-Encoding header")),
+        _ => Ok(raw),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn execute_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/items", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut wire = Vec::new();
+            let mut buf = [0; 4096];
+            while !wire.ends_with(b"{\"a\":1}") {
+                let n = socket.read(&mut buf).await.unwrap();
+                wire.extend_from_slice(&buf[..n]);
+            }
+            let wire = String::from_utf8(wire).unwrap();
+            assert!(wire.starts_with("PUT /items HTTP/1.1\r\n"));
+            assert!(wire.contains("x-default: 1\r\n") && wire.contains("x-own: 2\r\n"));
+            let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            gzip.write_all(b"{\"ok\":true}").unwrap();
+            let body = gzip.finish().unwrap();
+            let head = format!("HTTP/1.1 201 Created\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n", body.len());
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&body).await.unwrap();
+        });
+        let mut options = ClientOptions { timeout: Some(Duration::from_secs(5)), ..Default::default() };
+        options.headers.insert("x-default", "1".parse().unwrap());
+        let client = build_client(&options).unwrap();
+        let mut spec = RequestSpec::new(Method::PUT, url);
+        spec.headers.insert("x-own", "2".parse().unwrap());
+        spec.body = Some(b"{\"a\":1}".to_vec());
+        let summary = execute(&client, spec).await.unwrap();
+        assert_eq!(summary.status, StatusCode::CREATED);
+        assert_eq!(summary.json().unwrap(), serde_json::json!({"ok": true}));
+        assert!(execute(&client, RequestSpec::new(Method::GET, "http://127.0.0.1:1/")).await.is_err());
+    }
+
+    #[test]
+    fn decode_body_works() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(decode_body(&headers, b"a".to_vec()).unwrap(), b"a");
+        headers.insert(header::CONTENT_ENCODING, "identity".parse().unwrap());
+        assert_eq!(decode_body(&headers, b"a".to_vec()).unwrap(), b"a");
+        headers.insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        assert!(decode_body(&headers, b"a".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! The TOML config file: `--config PATH`, else `$HTTPIE_CONFIG`, else `config.toml` in the
+//! platform's config directory, like `~/.config/httpie/config.toml`. Its values are defaults
+//! that flags given on the command line override.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use crate::ColorChoice;
+
+/// Environment variable pointing at a config file to use instead of the default one.
+pub const CONFIG_ENV: &str = "HTTPIE_CONFIG";
+
+/// Defaults read from the config file; flags given on the command line win.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub timeout: Option<f64>,
+    pub follow_redirects: Option<bool>,
+    pub cache: Option<bool>,
+    pub color: Option<ColorChoice>,
+    pub auth: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    /// Headers sent only to hosts matching the key, a pattern like `*.staging.example.com`.
+    pub host_headers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Whether `host` matches `pattern`, where `*` stands for any run of characters. As in
+/// `NO_PROXY`, a pattern starting with `.` matches the domain and all its subdomains.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    match pattern.strip_prefix('.') {
+        Some(domain) => host == domain || glob(&format!("*.{}", domain), &host),
+        None => glob(&pattern, &host),
+    }
+}
+
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len()).filter(|&i| text.is_char_boundary(i)).any(|i| glob(rest, &text[i..]))
+        }
+    }
+}
+
+/// The per-OS config file, like `~/.config/httpie/config.toml` on Linux.
+fn default_path() -> Option<PathBuf> {
+    let dirs = directories::ProjectDirs::from("", "", "httpie")?;
+    Some(dirs.config_dir().join("config.toml"))
+}
+
+/// Where the config is read from: `explicit`, then `HTTPIE_CONFIG`, then the default path.
+/// The flag says whether the file was asked for, and so must exist.
+pub fn location(explicit: Option<&Path>) -> Option<(PathBuf, bool)> {
+    if let Some(path) = explicit {
+        return Some((path.to_path_buf(), true));
+    }
+    match std::env::var_os(CONFIG_ENV) {
+        Some(path) => Some((PathBuf::from(path), true)),
+        None => default_path().map(|path| (path, false)),
+    }
+}
+
+/// Loads the config, falling back to defaults when the default file doesn't exist.
+pub fn load(explicit: Option<&Path>) -> Result<Config> {
+    let Some((path, explicit)) = location(explicit) else {
+        return Ok(Config::default());
+    };
+    if !explicit && !path.exists() {
+        return Ok(Config::default());
+    }
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read config {}: {}", path.display(), e))?;
+    toml::from_str(&content).map_err(|e| anyhow!("Failed to parse config {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_works() {
+        let config: Config = toml::from_str(
+            r#"
+            timeout = 2.5
+            follow-redirects = false
+            color = "never"
+
+            [headers]
+            X-Env = "staging"
+
+            [host-headers."*.staging.example.com"]
+            X-Team = "api"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.timeout, Some(2.5));
+        assert_eq!(config.follow_redirects, Some(false));
+        assert_eq!(config.color, Some(ColorChoice::Never));
+        assert_eq!(config.headers["X-Env"], "staging");
+        assert_eq!(config.host_headers["*.staging.example.com"]["X-Team"], "api");
+        assert!(toml::from_str::<Config>("retries = 3").is_err());
+    }
+
+    #[test]
+    fn load_works() {
+        let path = std::env::temp_dir().join(format!("httpie-config-test-{}.toml", std::process::id()));
+        std::fs::write(&path, "timeout = 1.5").unwrap();
+        assert_eq!(load(Some(&path)).unwrap().timeout, Some(1.5));
+        assert_eq!(location(Some(&path)), Some((path.clone(), true)));
+        std::fs::remove_file(&path).unwrap();
+        let err = load(Some(&path)).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Failed to read config {}", path.display())));
+    }
+
+    #[test]
+    fn host_matches_works() {
+        assert!(host_matches("*.staging.example.com", "api.staging.example.com"));
+        assert!(host_matches("*.staging.example.com", "a.b.STAGING.example.com"));
+        assert!(!host_matches("*.staging.example.com", "staging.example.com"));
+        assert!(host_matches(".example.com", "example.com"));
+        assert!(host_matches(".example.com", "api.example.com"));
+        assert!(!host_matches(".example.com", "badexample.com"));
+        assert!(host_matches("api-*.example.com", "api-2.example.com"));
+        assert!(host_matches("localhost", "localhost"));
+        assert!(!host_matches("localhost", "localhost.example.com"));
+    }
+}
@@ -0,0 +1,264 @@
+//! Turning a curl command line into a request for `import-curl`.
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+
+/// The parts of a curl command this tool understands.
+#[derive(Debug, Default, PartialEq)]
+pub struct CurlRequest {
+    pub method: Option<String>,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// `-d` values joined with `&`, as curl sends them.
+    pub data: Option<String>,
+    pub user: Option<String>,
+}
+
+impl CurlRequest {
+    /// The explicit method, or what curl would pick from the options given.
+    pub fn method(&self) -> &str {
+        match (&self.method, &self.data) {
+            (Some(method), _) => method,
+            (None, Some(_)) => "POST",
+            (None, None) => "GET",
+        }
+    }
+
+    pub fn has_header(&self, name: &str) -> bool {
+        self.headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    /// The equivalent httpie command line. Form-style data becomes `--form` fields and any
+    /// other data is piped in on stdin.
+    pub fn to_command(&self) -> Result<String> {
+        let method = self.method().to_ascii_lowercase();
+        if !["get", "post", "put", "patch"].contains(&method.as_str()) {
+            return Err(anyhow!("httpie has no {} command; run import-curl without --show to send it", self.method()));
+        }
+        let mut words = vec!["httpie".to_string()];
+        for (name, value) in &self.headers {
+            words.push("-H".into());
+            words.push(format!("{}: {}", name, value));
+        }
+        if let Some(user) = &self.user {
+            words.push("--auth".into());
+            words.push(user.clone());
+        }
+        words.push(method.clone());
+        words.push(self.url.clone());
+        let mut stdin = None;
+        match &self.data {
+            Some(data) if method == "get" => return Err(anyhow!("httpie can't send a body with get, but the curl command has {}", data)),
+            Some(data) if !self.has_header("content-type") && data.split('&').all(|pair| pair.contains('=')) => {
+                let url = Url::parse(&format!("http://localhost/?{}", data))?;
+                words.push("--form".into());
+                words.extend(url.query_pairs().map(|(k, v)| format!("{}={}", k, v)));
+            }
+            Some(data) => stdin = Some(data),
+            None => {}
+        }
+        let command = words.iter().map(|w| shell_quote(w)).collect::<Vec<_>>().join(" ");
+        Ok(match stdin {
+            Some(data) => format!("printf '%s' {} | {}", shell_quote(data), command),
+            None => command,
+        })
+    }
+}
+
+/// Quotes `s` for a POSIX shell unless it's made only of characters that are safe bare.
+fn shell_quote(s: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !s.is_empty() && s.chars().all(safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// Splits a command line the way a POSIX shell would: whitespace separates words, quotes
+/// group them and a backslash escapes the next character or joins lines.
+pub fn tokenize(s: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let word = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unterminated ' quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(anyhow!("Unterminated \" quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unterminated \" quote")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+            },
+            c if c.is_whitespace() => tokens.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    tokens.extend(current);
+    Ok(tokens)
+}
+
+/// Options that take a value but don't change the request, so they're skipped with it.
+const IGNORED_WITH_VALUE: &[&str] = &["-o", "--output", "-m", "--max-time", "--connect-timeout", "-w", "--write-out"];
+
+/// Parses curl's arguments, returning the request and warnings about options it skipped.
+pub fn parse(tokens: &[String]) -> Result<(CurlRequest, Vec<String>)> {
+    let mut request = CurlRequest::default();
+    let mut warnings = Vec::new();
+    let mut data: Vec<String> = Vec::new();
+    let mut args = tokens.iter().map(String::as_str).peekable();
+    if args.peek() == Some(&"curl") {
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            request.url = arg.to_string();
+            continue;
+        }
+        // `--name=value` and `-Xvalue` carry the value in the same word.
+        let short_end = arg.char_indices().nth(2).map(|(i, _)| i);
+        let (flag, attached) = match (arg.split_once('='), short_end) {
+            (Some((flag, value)), _) if arg.starts_with("--") => (flag, Some(value.to_string())),
+            (_, Some(end)) if !arg.starts_with("--") => (&arg[..end], Some(arg[end..].to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || match &attached {
+            Some(value) => Ok(value.clone()),
+            None => args.next().map(str::to_string).ok_or_else(|| anyhow!("{} is missing its value", flag)),
+        };
+        match flag {
+            "-X" | "--request" => request.method = Some(value()?.to_ascii_uppercase()),
+            "-H" | "--header" => {
+                let header = value()?;
+                let (name, v) = header
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Failed to parse header {}", header))?;
+                request.headers.push((name.trim().to_string(), v.trim().to_string()));
+            }
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii" => data.push(value()?),
+            "--json" => {
+                data.push(value()?);
+                for (name, v) in [("Content-Type", "application/json"), ("Accept", "application/json")] {
+                    if !request.has_header(name) {
+                        request.headers.push((name.to_string(), v.to_string()));
+                    }
+                }
+            }
+            "-u" | "--user" => request.user = Some(value()?),
+            "--url" => request.url = value()?,
+            "-A" | "--user-agent" => request.headers.push(("User-Agent".to_string(), value()?)),
+            "-e" | "--referer" => request.headers.push(("Referer".to_string(), value()?)),
+            "-b" | "--cookie" => request.headers.push(("Cookie".to_string(), value()?)),
+            "-I" | "--head" => request.method = Some("HEAD".to_string()),
+            flag if IGNORED_WITH_VALUE.contains(&flag) => {
+                value()?;
+                warnings.push(format!("ignoring curl option {}", flag));
+            }
+            // Short flags may be bundled like `-sSL`, so warn about the whole word.
+            _ => warnings.push(format!("ignoring curl option {}", arg)),
+        }
+    }
+    if request.url.is_empty() {
+        return Err(anyhow!("The curl command has no URL"));
+    }
+    if !data.is_empty() {
+        request.data = Some(data.join("&"));
+    }
+    Ok((request, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_works() {
+        assert_eq!(
+            tokenize(r#"curl -H "A: \"b\"" -d 'x=1 2' \
+              https://a.b/c\ d"#)
+            .unwrap(),
+            ["curl", "-H", "A: \"b\"", "-d", "x=1 2", "https://a.b/c d"]
+        );
+        assert_eq!(tokenize("a''b \"\" ").unwrap(), ["ab", ""]);
+        assert!(tokenize("'open").is_err());
+    }
+
+    #[test]
+    fn parse_works() {
+        let tokens = tokenize("curl -sSL -XPOST https://a.b -H 'A: B' -d x=1 --data=y=2 -u alice:pw -m 5").unwrap();
+        let (request, warnings) = parse(&tokens).unwrap();
+        assert_eq!(
+            request,
+            CurlRequest {
+                method: Some("POST".into()),
+                url: "https://a.b".into(),
+                headers: vec![("A".into(), "B".into())],
+                data: Some("x=1&y=2".into()),
+                user: Some("alice:pw".into()),
+            }
+        );
+        assert_eq!(warnings, ["ignoring curl option -sSL", "ignoring curl option -m"]);
+
+        let (request, _) = parse(&tokenize("curl --url https://a.b --json '{}'").unwrap()).unwrap();
+        assert_eq!(request.method(), "POST");
+        assert!(request.has_header("content-type"));
+        assert!(parse(&tokenize("curl -X GET").unwrap()).is_err());
+        assert!(parse(&tokenize("curl https://a.b -H").unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_skips_non_ascii_flags() {
+        for flag in ["-é", "-éx", "-Xé"] {
+            let tokens = ["curl".to_string(), flag.to_string(), "http://x/".to_string()];
+            let (request, warnings) = parse(&tokens).unwrap();
+            assert_eq!(request.url, "http://x/");
+            if flag == "-Xé" {
+                assert_eq!(request.method.as_deref(), Some("é"));
+            } else {
+                assert_eq!(warnings, [format!("ignoring curl option {}", flag)]);
+            }
+        }
+    }
+
+    #[test]
+    fn to_command_works() {
+        let command = |s: &str| parse(&tokenize(s).unwrap()).unwrap().0.to_command();
+        assert_eq!(
+            command("curl https://a.b -H 'X-Tag: a b' -d x=1 -d 'y=a%20b'").unwrap(),
+            "httpie -H 'X-Tag: a b' post https://a.b --form x=1 'y=a b'"
+        );
+        assert_eq!(
+            command("curl -X PUT https://a.b -H 'Content-Type: application/json' -d '{\"it'\\''s\": 1}'").unwrap(),
+            r#"printf '%s' '{"it'\''s": 1}' | httpie -H 'Content-Type: application/json' put https://a.b"#
+        );
+        assert_eq!(command("curl -u alice https://a.b").unwrap(), "httpie --auth alice get https://a.b");
+        assert!(command("curl -X DELETE https://a.b").is_err());
+        assert!(parse(&tokenize("curl https://a.b -H").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Transport logging for `--debug`: reqwest's own log messages, DNS lookups and the timing of
+//! each exchange, all on stderr. Nothing is installed unless the flag is given.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use colored::Colorize;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+
+/// Prints reqwest's and our own debug messages with the time since startup.
+struct StderrLogger {
+    start: Instant,
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Debug
+            && (metadata.target().starts_with("reqwest") || metadata.target() == TARGET)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = format!("[{:>9.2?}] {}", self.start.elapsed(), record.args());
+            eprintln!("{}", line.dimmed());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+const TARGET: &str = "httpie";
+
+/// Installs the logger; call once, before the client is built.
+pub fn init() {
+    let logger = Box::leak(Box::new(StderrLogger { start: Instant::now() }));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+}
+
+/// Logs a step of an exchange next to reqwest's own messages.
+pub fn phase(args: std::fmt::Arguments) {
+    log::debug!(target: TARGET, "{}", args);
+}
+
+/// Resolves names like the default resolver, logging what each lookup returned and how long
+/// it took.
+pub struct TimedResolver;
+
+impl TimedResolver {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self)
+    }
+}
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let start = Instant::now();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            phase(format_args!("resolved {} to {:?} in {:.2?}", host, addrs, start.elapsed()));
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timed_resolver_works() {
+        let addrs: Vec<_> = TimedResolver.resolve("localhost".parse().unwrap()).await.unwrap().collect();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+        assert!(!addrs.is_empty());
+    }
+}
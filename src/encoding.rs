@@ -0,0 +1,88 @@
+//! Decoding compressed response bodies named by `Content-Encoding`, and compressing request
+//! bodies for `--compress`.
+
+use std::io::{Read, Write};
+use anyhow::{anyhow, Result};
+
+/// Encodings are listed in the order they were applied, so they're undone from the last one.
+pub fn decode(encodings: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let mut body = body.to_vec();
+    for encoding in encodings.split(',').map(str::trim).rev() {
+        body = decode_one(encoding, &body)?;
+    }
+    Ok(body)
+}
+
+fn decode_one(encoding: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match encoding.to_ascii_lowercase().as_str() {
+        "" | "identity" => out.extend_from_slice(body),
+        "gzip" | "x-gzip" => {
+            flate2::read::GzDecoder::new(body).read_to_end(&mut out)?;
+        }
+        "deflate" => {
+            flate2::read::ZlibDecoder::new(body).read_to_end(&mut out)?;
+        }
+        "br" => {
+            brotli::Decompressor::new(body, 4096).read_to_end(&mut out)?;
+        }
+        "zstd" => {
+            zstd::stream::read::Decoder::new(body)?.read_to_end(&mut out)?;
+        }
+        other => return Err(anyhow!("Unsupported content encoding {}", other)),
+    }
+    Ok(out)
+}
+
+/// Compresses `body` with `encoding`, which is `gzip` or `br`.
+pub fn encode(encoding: &str, body: &[u8]) -> Result<Vec<u8>> {
+    match encoding {
+        "gzip" => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body)?;
+            Ok(encoder.finish()?)
+        }
+        "br" => {
+            let mut out = Vec::new();
+            // Dropping the writer flushes the last block.
+            brotli::CompressorWriter::new(&mut out, 4096, 5, 22).write_all(body)?;
+            Ok(out)
+        }
+        other => Err(anyhow!("Unsupported content encoding {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_works() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"hello").unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(decode("gzip", &gzip).unwrap(), b"hello");
+
+        let mut br = Vec::new();
+        brotli::CompressorWriter::new(&mut br, 4096, 5, 22).write_all(b"hello").unwrap();
+        assert_eq!(decode("br", &br).unwrap(), b"hello");
+
+        let zstd = zstd::encode_all(&b"hello"[..], 0).unwrap();
+        assert_eq!(decode("zstd", &zstd).unwrap(), b"hello");
+
+        let mut both = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        both.write_all(&zstd).unwrap();
+        assert_eq!(decode("zstd, gzip", &both.finish().unwrap()).unwrap(), b"hello");
+
+        assert!(decode("compress", b"x").is_err());
+        assert!(decode("gzip", b"not gzip").is_err());
+    }
+
+    #[test]
+    fn encode_works() {
+        for encoding in ["gzip", "br"] {
+            assert_eq!(decode(encoding, &encode(encoding, b"hello").unwrap()).unwrap(), b"hello");
+        }
+        assert!(encode("zstd", b"x").is_err());
+    }
+}
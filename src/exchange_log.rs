@@ -0,0 +1,84 @@
+//! Appending a plain-text record of each exchange to the `--log` file.
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+use anyhow::{anyhow, Result};
+use reqwest::header::HeaderMap;
+use reqwest::{Request, Response};
+
+fn write_headers(out: &mut String, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let _ = writeln!(out, "{}: {}", name, String::from_utf8_lossy(value.as_bytes()));
+    }
+    out.push('\n');
+}
+
+fn write_body(out: &mut String, body: &[u8]) {
+    if !body.is_empty() {
+        out.push_str(&String::from_utf8_lossy(body));
+        out.push_str("\n\n");
+    }
+}
+
+/// Formats one exchange without colors. `request` is `None` when its body was a stream that
+/// couldn't be kept.
+pub fn record(time: SystemTime, request: Option<&Request>, response: &Response, body: &[u8]) -> String {
+    let mut out = format!("=== {} ===\n", httpdate::fmt_http_date(time));
+    if let Some(req) = request {
+        let _ = writeln!(out, "{} {} {:?}", req.method(), req.url(), req.version());
+        write_headers(&mut out, req.headers());
+        write_body(&mut out, req.body().and_then(|b| b.as_bytes()).unwrap_or_default());
+    }
+    let _ = writeln!(out, "{:?} {}", response.version(), response.status());
+    write_headers(&mut out, response.headers());
+    write_body(&mut out, body);
+    out
+}
+
+/// Appends `record` to the file at `path` in a single write, so concurrent requests don't
+/// interleave.
+pub fn append(path: &Path, record: &str) -> Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(record.as_bytes()))
+        .map_err(|e| anyhow!("Failed to write log {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use reqwest::Client;
+
+    #[test]
+    fn record_works() {
+        let request = Client::new()
+            .post("http://a.b/users")
+            .header("x-tag", "a")
+            .body("{\"name\":\"alice\"}")
+            .build()
+            .unwrap();
+        let response: Response = http::Response::builder()
+            .status(201)
+            .header("content-type", "application/json")
+            .body("")
+            .unwrap()
+            .into();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+        assert_eq!(
+            record(time, Some(&request), &response, b"{\"id\":1}"),
+            "=== Fri, 02 Jan 1970 00:00:00 GMT ===\n\
+             POST http://a.b/users HTTP/1.1\n\
+             x-tag: a\n\n\
+             {\"name\":\"alice\"}\n\n\
+             HTTP/1.1 201 Created\n\
+             content-type: application/json\n\n\
+             {\"id\":1}\n\n"
+        );
+    }
+}
@@ -0,0 +1,4975 @@
+//! A command-line HTTP client. The CLI itself is [`cli_main`]; [`client`] has the
+//! request/response core for use from other programs.
+
+mod cache;
+pub mod client;
+mod config;
+mod curl;
+mod debug;
+mod digest;
+mod encoding;
+mod exchange_log;
+mod netrc;
+mod oauth2;
+mod session;
+#[cfg(unix)]
+mod unix;
+mod ws;
+
+use anyhow::{anyhow, Result};
+use reqwest::{Client, header, Method, Request, RequestBuilder, Response, ResponseBuilderExt, StatusCode, Url, Version};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::Semaphore;
+use std::time::{Duration, Instant, SystemTime};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use colored::Colorize;
+use futures_util::{stream, SinkExt, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use mime::{Mime, APPLICATION_JSON};
+use similar::{ChangeTag, TextDiff};
+use client::ResponseSummary;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+#[derive(Parser, Debug)]
+#[command(name = "HTTPie")]
+#[command(author = "Pengsha Ying <yingfusheng@foxmail.com>")]
+#[command(version = "1.0")]
+#[command(about = "HTTPie is making APIs simple and intuitive for those building the tools of our time.", long_about = None)]
+#[command(after_help = "Exit status: 0 on success, 1 on other errors, including --expect-status, 6 when the host \
+can't be resolved, 7 when the connection fails, 28 on a timeout, 35 on a TLS error and 130 when interrupted.")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Print only the part of a JSON response selected by a path like `.data.items[0].id`
+    #[arg(long, global = true, value_parser = parse_filter)]
+    filter: Option<Filter>,
+
+    /// Print only the part of a JSON response at a JSON Pointer like `/data/items/0/name`
+    #[arg(long, global = true, value_name = "POINTER", value_parser = parse_pointer, conflicts_with = "filter")]
+    select: Option<String>,
+
+    /// What to print: any of `H` request headers, `B` request body, `h` response headers, `b` response body
+    #[arg(long, global = true, value_parser = parse_print_flags, default_value = "hb")]
+    print: PrintFlags,
+
+    /// Print the whole exchange, like `--print HBhb --meta`
+    #[arg(short, long, global = true, conflicts_with = "print")]
+    verbose: bool,
+
+    /// Print the body size, content type and HTTP version after the body
+    #[arg(long, global = true)]
+    meta: bool,
+
+    /// Print errors to stderr as `{"error": ..., "kind": ...}` with kind one of network,
+    /// timeout, tls, parse, http_status or other
+    #[arg(long, global = true)]
+    error_json: bool,
+
+    /// Show the full error chain instead of a short message, and log DNS lookups, new
+    /// connections, redirects and timings to stderr
+    #[arg(long, global = true)]
+    debug: bool,
+
+    /// Ask for a JSON response; post bodies are already sent as JSON
+    #[arg(long, global = true, conflicts_with_all = ["xml", "accept"])]
+    json: bool,
+
+    /// Ask for an XML response
+    #[arg(long, global = true, conflicts_with = "accept")]
+    xml: bool,
+
+    /// Set the Accept header; shorthands like `json` or `xml` expand to the full MIME type
+    #[arg(long, global = true, value_parser = parse_accept)]
+    accept: Option<HeaderValue>,
+
+    /// Credentials as `user:password`, sent as --auth-type says; with just `user` the password is prompted for
+    #[arg(short, long, global = true, value_parser = parse_auth)]
+    auth: Option<Auth>,
+
+    /// How to send the --auth or netrc credentials
+    #[arg(long, global = true, value_enum, default_value_t = AuthType::Basic)]
+    auth_type: AuthType,
+
+    /// Sort object keys when pretty-printing JSON
+    #[arg(long, global = true)]
+    sorted_json: bool,
+
+    /// Print JSON on a single line instead of pretty-printing it
+    #[arg(long, global = true)]
+    compact: bool,
+
+    /// Stream the raw response body into this file instead of printing it
+    #[arg(short, long, global = true, conflicts_with_all = ["filter", "select"])]
+    output: Option<PathBuf>,
+
+    /// Add the body to the end of the --output file instead of replacing it (`-a` is --auth)
+    #[arg(long, global = true, requires = "output", conflicts_with = "range")]
+    append: bool,
+
+    /// With --append, write a `=== date url ===` line before each body
+    #[arg(long, global = true, requires = "append")]
+    append_stamp: bool,
+
+    /// Save the response body to a file named after the URL
+    #[arg(short, long, global = true, conflicts_with_all = ["filter", "select"])]
+    download: bool,
+
+    /// Read the config from this file, which must exist, instead of the default one
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Write the status line and response headers to this file, whatever --print says
+    #[arg(long, global = true, visible_alias = "dump-header", value_name = "PATH")]
+    output_headers: Option<PathBuf>,
+
+    /// Give up on the request after this many seconds
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+
+    /// Abort the whole run, body download included, after this many seconds
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds)]
+    max_time: Option<Duration>,
+
+    /// Stop reading a response body after this many bytes, failing once what was read is shown
+    #[arg(long, global = true, value_name = "BYTES")]
+    max_response_size: Option<u64>,
+
+    /// Stop reading a `text/event-stream` response after N events
+    #[arg(long, global = true, value_name = "N")]
+    max_events: Option<NonZeroUsize>,
+
+    /// Read streamed and downloaded bodies no faster than this many bytes a second; takes
+    /// suffixes like `500k` or `2m`
+    #[arg(long, global = true, value_name = "RATE", value_parser = parse_rate)]
+    limit_rate: Option<u64>,
+
+    /// Give up on connecting after this many seconds, independently of --timeout
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds)]
+    connect_timeout: Option<Duration>,
+
+    /// Follow redirects (the default)
+    #[arg(long, global = true, conflicts_with = "no_follow")]
+    follow: bool,
+
+    /// Don't follow redirects
+    #[arg(long, global = true)]
+    no_follow: bool,
+
+    /// Cache GET responses on disk as `Cache-Control` and `ETag` allow, except for requests
+    /// with credentials or cookies
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    cache: bool,
+
+    /// Don't cache, even when the config says to
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Empty the response cache before sending the request
+    #[arg(long, global = true)]
+    cache_clear: bool,
+
+    /// When to color the output
+    #[arg(long, global = true, value_enum)]
+    color: Option<ColorChoice>,
+
+    /// Whether to color and reindent bodies: `all` does both even when piped, `colors` only
+    /// colors, `format` only reindents and `none` prints bodies as received
+    #[arg(long, global = true, value_enum, conflicts_with = "color")]
+    pretty: Option<Pretty>,
+
+    /// Send this Host header instead of the one derived from the URL
+    #[arg(long, global = true, value_name = "VALUE")]
+    host_header: Option<HeaderValue>,
+
+    /// Connect to ADDR whenever HOST is requested, like curl's `--resolve`; may be repeated
+    #[arg(long, global = true, value_name = "HOST:PORT:ADDR", value_parser = parse_resolve)]
+    resolve: Vec<Resolve>,
+
+    /// Only speak HTTP/1.x
+    #[arg(long, global = true, conflicts_with = "http2")]
+    http1: bool,
+
+    /// Speak HTTP/2 without negotiating it first (prior knowledge)
+    #[arg(long, global = true)]
+    http2: bool,
+
+    /// Never read stdin: a piped stdin is no longer sent as the body and `--body-file -` is refused
+    #[arg(short = 'I', long, global = true)]
+    ignore_stdin: bool,
+
+    /// Connect through this Unix domain socket; the URL may then be just a path like `/v1/status`.
+    /// TLS, proxies, redirects and --timeout are not supported over a socket
+    #[arg(long, global = true, value_name = "PATH")]
+    unix_socket: Option<PathBuf>,
+
+    /// How many requests may be in flight at once when several are sent
+    #[arg(long, global = true, value_name = "N")]
+    concurrency: Option<NonZeroUsize>,
+
+    /// With several URLs, have at most N requests in flight to any one host; --concurrency
+    /// still limits them overall
+    #[arg(long, global = true, value_name = "N")]
+    max_per_host: Option<NonZeroUsize>,
+
+    /// Open a new connection for every request, sending `Connection: close`
+    #[arg(long, global = true)]
+    no_keepalive: bool,
+
+    /// Keep at most this many idle connections per host for reuse
+    #[arg(long, global = true, value_name = "N", conflicts_with = "no_keepalive")]
+    pool_max_idle: Option<usize>,
+
+    /// Send the request N times and print latency and status statistics instead of the response
+    #[arg(long, global = true, value_name = "N")]
+    repeat: Option<NonZeroUsize>,
+
+    /// With --repeat, wait this many milliseconds between requests; with --concurrency, start
+    /// one request every MS milliseconds instead
+    #[arg(long, global = true, visible_alias = "wait", value_name = "MS", requires = "repeat")]
+    delay: Option<u64>,
+
+    /// Start at most this many requests a second with --repeat or several URLs; the limit is
+    /// shared by all --concurrency workers, not given to each
+    #[arg(long, global = true, value_name = "RPS", value_parser = parse_rps, conflicts_with = "delay")]
+    rate_limit: Option<Duration>,
+
+    /// Add a header, replacing any the request would otherwise send with the same name
+    #[arg(short = 'H', long = "header", global = true, value_name = "NAME:VALUE", value_parser = parse_header)]
+    headers: Vec<HeaderPair>,
+
+    /// Read headers from a file of `Name: Value` lines; blank lines and `#` comments are
+    /// skipped, and `-H` headers win over the file's
+    #[arg(long, global = true, value_name = "PATH", visible_alias = "headers-file")]
+    headers_from: Option<PathBuf>,
+
+    /// How `$VAR` and `${VAR}` in body and header values are expanded when VAR isn't set:
+    /// `empty`, the default, drops it with a warning, `lenient` leaves it as written and
+    /// `strict` is an error. `$$` is a literal `$`
+    #[arg(long, global = true, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "strict")]
+    expand_env: Option<ExpandMode>,
+
+    /// Send `$VAR` and `${VAR}` in body and header values as written
+    #[arg(long, global = true, conflicts_with_all = ["expand_env", "strict_env"])]
+    no_env_expand: bool,
+
+    /// Fail when a variable in a body or header value isn't set, like --expand-env=strict
+    #[arg(long, global = true, conflicts_with = "expand_env")]
+    strict_env: bool,
+
+    /// Fail unless the response status is CODE, which may also be a class like `2xx`
+    #[arg(long, global = true, value_name = "CODE", value_parser = parse_status_matcher)]
+    expect_status: Option<StatusMatcher>,
+
+    /// Validate the JSON response body against the JSON Schema in this file, printing each
+    /// violation and failing when there are any
+    #[arg(long, global = true, value_name = "PATH", conflicts_with_all = ["output", "download", "quiet"])]
+    schema: Option<PathBuf>,
+
+    /// Append each request and its response to this file, without colors
+    #[arg(long, global = true, value_name = "PATH")]
+    log: Option<PathBuf>,
+
+    /// Take Basic auth credentials for the request's host from `~/.netrc` when none are given
+    #[arg(long, global = true)]
+    netrc: bool,
+
+    /// Like --netrc, reading this file instead
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "netrc")]
+    netrc_file: Option<PathBuf>,
+
+    /// Fetch a token from this OAuth2 endpoint with the client-credentials grant and send it
+    /// as a Bearer token; with --session, the token is kept there and reused until it expires
+    #[arg(long, global = true, value_name = "URL", requires_all = ["oauth2_client_id", "oauth2_client_secret"], conflicts_with_all = ["auth", "netrc", "netrc_file"])]
+    oauth2_token_url: Option<String>,
+
+    /// The client ID for --oauth2-token-url
+    #[arg(long, global = true, value_name = "ID", requires = "oauth2_token_url")]
+    oauth2_client_id: Option<String>,
+
+    /// The client secret for --oauth2-token-url
+    #[arg(long, global = true, value_name = "SECRET", requires = "oauth2_token_url")]
+    oauth2_client_secret: Option<String>,
+
+    /// The scope to ask --oauth2-token-url for
+    #[arg(long, global = true, value_name = "SCOPE", requires = "oauth2_token_url")]
+    oauth2_scope: Option<String>,
+
+    /// Ask for part of the body, like `0-1023`, `500-` or `-500`. With --output or --download
+    /// an existing file is resumed from its current size
+    #[arg(long, global = true, value_name = "RANGE", value_parser = parse_range)]
+    range: Option<ByteRange>,
+
+    /// Scheme added to URLs given without one, like `example.com/api`; a scheme written in the
+    /// URL always wins
+    #[arg(long, global = true, value_enum, default_value_t = Scheme::Https)]
+    default_scheme: Scheme,
+
+    /// Print the URL each request goes to, after adding the default scheme and encoding, to stderr
+    #[arg(long, global = true)]
+    show_url: bool,
+
+    /// Send a new UUID as X-Request-ID, unless -H gives one, print it to stderr and check that
+    /// any ID the response echoes back matches
+    #[arg(long, global = true, visible_alias = "print-request-id")]
+    request_id: bool,
+
+    /// Replace `{NAME}` in the URL with VALUE, percent-encoded; may be repeated
+    #[arg(long, global = true, value_name = "NAME=VALUE", value_parser = parse_path_var)]
+    path_var: Vec<(String, String)>,
+
+    /// Send the headers and cookies kept in this session, a name or a `.json` path, and keep
+    /// the response's cookies and this request's -H headers in it
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "session_read_only")]
+    session: Option<String>,
+
+    /// Like --session, but leave the session file as it is
+    #[arg(long, global = true, value_name = "NAME")]
+    session_read_only: Option<String>,
+
+    /// Print nothing, leaving only the exit status; --output and --download still write the body
+    #[arg(short, long, global = true, conflicts_with_all = ["print", "verbose", "meta", "repeat"])]
+    quiet: bool,
+
+    /// Print headers in the order they were sent or received instead of sorted by name
+    #[arg(long, global = true)]
+    unsorted_headers: bool,
+
+    /// Print only the body lines matching this regex, with the matches highlighted; JSON is
+    /// searched after pretty-printing
+    #[arg(long, global = true, value_name = "PATTERN", value_parser = parse_grep, conflicts_with_all = ["output", "download"])]
+    grep: Option<Regex>,
+
+    /// Print the body lines that don't match --grep instead
+    #[arg(long, global = true, requires = "grep")]
+    grep_invert: bool,
+
+    /// Also send `X-Powered-By: Rust`
+    #[arg(long, global = true)]
+    signature: bool,
+
+    /// How to lay out what is printed: HTTPie's own style, `curl -v` style with `>`/`<`
+    /// prefixes, or one JSON object per message, pretty-printed or on a single line
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Httpie)]
+    output_format: OutputFormat,
+
+    /// Compress request bodies larger than 1 KiB, or every body with `--compress=always`
+    #[arg(long, global = true, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "auto")]
+    compress: Option<CompressMode>,
+
+    /// The Content-Encoding --compress uses
+    #[arg(long, global = true, value_enum, default_value_t = Coding::Gzip, requires = "compress")]
+    compress_with: Coding,
+
+    /// Send If-Modified-Since; a 304 response is reported as cached
+    #[arg(long, global = true, value_name = "HTTP-DATE", value_parser = parse_http_date)]
+    if_modified_since: Option<HeaderValue>,
+
+    /// Send If-None-Match; a bare tag is quoted for you
+    #[arg(long, global = true, visible_alias = "etag", value_name = "ETAG", value_parser = parse_etag)]
+    if_none_match: Option<HeaderValue>,
+
+    /// Cut printed header values longer than N characters short with an ellipsis
+    #[arg(long, global = true, value_name = "N")]
+    truncate_headers: Option<usize>,
+
+    /// Print this header's value as `[redacted]`; may be repeated
+    #[arg(long, global = true, value_name = "NAME")]
+    hide_header: Vec<HeaderName>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum CompressMode {
+    Auto,
+    Always,
+}
+
+/// Bodies below this size aren't worth compressing unless asked to.
+const COMPRESS_THRESHOLD: usize = 1024;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Coding {
+    Gzip,
+    Br,
+}
+
+impl Coding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Coding::Gzip => "gzip",
+            Coding::Br => "br",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum AuthType {
+    Basic,
+    /// Answer the server's 401 Digest challenge, retrying the request once
+    Digest,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Httpie,
+    Curl,
+    Json,
+    Ndjson,
+}
+
+impl OutputFormat {
+    fn is_json(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Ndjson)
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    /// Prepends the scheme unless `url` already names one.
+    fn apply(self, url: &str) -> String {
+        if url.contains("://") {
+            return url.to_string();
+        }
+        match self {
+            Self::Http => format!("http://{}", url),
+            Self::Https => format!("https://{}", url),
+        }
+    }
+}
+
+/// A byte range: `start-end`, an open-ended `start-`, or the last `-N` bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ByteRange {
+    From(u64, Option<u64>),
+    Last(u64),
+}
+
+impl FromStr for ByteRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!(format!("Failed to parse range {}, expected START-END, START- or -N", s));
+        let (start, end) = s.split_once('-').ok_or_else(err)?;
+        let range = match (start, end) {
+            ("", last) => Self::Last(last.parse().map_err(|_| err())?),
+            (start, "") => Self::From(start.parse().map_err(|_| err())?, None),
+            (start, end) => Self::From(start.parse().map_err(|_| err())?, Some(end.parse().map_err(|_| err())?)),
+        };
+        match range {
+            Self::From(start, Some(end)) if end < start => Err(err()),
+            _ => Ok(range),
+        }
+    }
+}
+
+impl ByteRange {
+    /// The range left to fetch when the first `done` bytes of it are already on disk.
+    fn resume(&self, done: u64) -> Option<Self> {
+        match *self {
+            Self::From(start, end) if end.is_none_or(|end| start + done <= end) => Some(Self::From(start + done, end)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::From(start, Some(end)) => write!(f, "bytes={}-{}", start, end),
+            Self::From(start, None) => write!(f, "bytes={}-", start),
+            Self::Last(n) => write!(f, "bytes=-{}", n),
+        }
+    }
+}
+
+/// An expected status: an exact code like `204` or a class like `2xx`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatusMatcher {
+    Exact(u16),
+    Class(u16),
+}
+
+impl FromStr for StatusMatcher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!(format!("Failed to parse status {}, expected a code like 200 or a class like 2xx", s));
+        let matcher = match s.to_ascii_lowercase().strip_suffix("xx") {
+            Some(class) => Self::Class(class.parse().map_err(|_| err())?),
+            None => Self::Exact(s.parse().map_err(|_| err())?),
+        };
+        match matcher {
+            Self::Class(1..=5) | Self::Exact(100..=599) => Ok(matcher),
+            _ => Err(err()),
+        }
+    }
+}
+
+impl StatusMatcher {
+    fn matches(&self, status: StatusCode) -> bool {
+        match self {
+            Self::Exact(code) => status.as_u16() == *code,
+            Self::Class(class) => status.as_u16() / 100 == *class,
+        }
+    }
+}
+
+impl std::fmt::Display for StatusMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(code) => write!(f, "{}", code),
+            Self::Class(class) => write!(f, "{}xx", class),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum ExpandMode {
+    Strict,
+    Lenient,
+    Empty,
+}
+
+impl Cli {
+    /// How variables in values are expanded, or `None` with --no-env-expand.
+    fn expand_mode(&self) -> Option<ExpandMode> {
+        match (self.no_env_expand, self.strict_env, self.expand_env) {
+            (true, _, _) => None,
+            (_, true, _) => Some(ExpandMode::Strict),
+            (_, _, mode) => Some(mode.unwrap_or(ExpandMode::Empty)),
+        }
+    }
+
+    fn json_format(&self) -> JsonFormat<'_> {
+        JsonFormat {
+            filter: self.filter.as_ref(),
+            pointer: self.select.as_deref(),
+            sorted: self.sorted_json,
+            compact: self.compact,
+            raw: matches!(self.pretty, Some(Pretty::Colors | Pretty::None)),
+        }
+    }
+
+    fn header_display(&self) -> HeaderDisplay<'_> {
+        HeaderDisplay {
+            sort: !self.unsorted_headers,
+            truncate: self.truncate_headers,
+            hidden: &self.hide_header,
+        }
+    }
+
+    fn grep(&self) -> Option<Grep<'_>> {
+        Some(Grep {
+            pattern: self.grep.as_ref()?,
+            invert: self.grep_invert,
+        })
+    }
+
+    fn print_flags(&self) -> PrintFlags {
+        if self.quiet {
+            PrintFlags::default()
+        } else if self.verbose {
+            PrintFlags {
+                request_headers: true,
+                request_body: true,
+                response_headers: true,
+                response_body: true,
+            }
+        } else {
+            self.print
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Pretty {
+    All,
+    Colors,
+    Format,
+    None,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ColorChoice {
+    /// Color when stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    Get(Get),
+    Post(Post),
+    Put(Post),
+    Patch(Post),
+    /// Post a GraphQL query
+    Graphql(Graphql),
+    /// Send the request a curl command line describes
+    ImportCurl(ImportCurl),
+    /// Fetch two URLs and show how their responses differ, failing when they do
+    Diff(Diff),
+    /// Open a WebSocket, send messages and print what comes back until it's closed
+    Ws(Ws),
+    /// Send an OPTIONS request and list the methods and CORS rules the server reports
+    Options(Options),
+    /// Inspect the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the path the config is read from
+    Path,
+}
+
+#[derive(Args, Debug)]
+struct Ws {
+    /// A `ws://` or `wss://` URL
+    #[arg(value_parser = parse_url)]
+    url: String,
+
+    /// Send this text message once connected; may be repeated. Without one, each line of a
+    /// piped stdin is sent
+    #[arg(long, short = 'm', value_name = "TEXT")]
+    message: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct Diff {
+    #[arg(value_parser = parse_url)]
+    left: String,
+
+    #[arg(value_parser = parse_url)]
+    right: String,
+
+    /// Compare the status lines and headers too, not just the bodies
+    #[arg(long)]
+    diff_headers: bool,
+}
+
+#[derive(Args, Debug)]
+struct ImportCurl {
+    /// The whole curl command, quoted as one argument
+    command: String,
+
+    /// Print the equivalent httpie command instead of sending the request
+    #[arg(long)]
+    show: bool,
+}
+
+#[derive(Args, Debug)]
+struct Options {
+    #[arg(value_parser = parse_url)]
+    url: String,
+}
+
+#[derive(Args, Debug)]
+struct Get {
+    /// Several URLs are fetched concurrently and printed in order
+    #[arg(value_parser = parse_url, required = true)]
+    url: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct Post {
+    #[arg(value_parser = parse_url)]
+    url: String,
+
+    /// Body fields, `k=v` or `k:=json`, and files to upload as `field@path`, optionally
+    /// followed by `;type=MIME`; a file makes the body multipart
+    #[arg(value_parser = parse_body_item)]
+    body: Vec<BodyItem>,
+
+    /// Send the body as `application/x-www-form-urlencoded` instead of JSON
+    #[arg(long)]
+    form: bool,
+
+    /// Send the fields as `multipart/form-data` even without a file among them
+    #[arg(long, conflicts_with_all = ["form", "content_type"])]
+    multipart: bool,
+
+    /// Send the JSON body with this Content-Type instead of `application/json`; shorthands
+    /// like `json` or `text` expand to the full MIME type
+    #[arg(long, conflicts_with = "form", value_parser = parse_mime)]
+    content_type: Option<Mime>,
+
+    /// Send the value of this environment variable as the raw body
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["body", "form", "multipart"])]
+    body_env: Option<String>,
+
+    /// Send the contents of this file as the raw body; `-` reads stdin
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["body", "form", "multipart", "body_env"])]
+    body_file: Option<PathBuf>,
+
+    /// Send the text on the system clipboard as the raw body; needs the `clipboard` feature
+    #[arg(long, conflicts_with_all = ["body", "form", "multipart", "body_env", "body_file"])]
+    body_clipboard: bool,
+
+    /// Send this string as the body, exactly as written
+    #[arg(long, value_name = "STRING", conflicts_with_all = ["body", "form", "multipart", "body_env", "body_file", "body_clipboard"])]
+    raw: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct Graphql {
+    #[arg(value_parser = parse_url)]
+    url: String,
+
+    /// The query, or `@path` to read it from a file
+    query: String,
+
+    /// Variables, given like body fields: `name=value` or `name:=json`
+    #[arg(value_parser = parse_kv_pair)]
+    variables: Vec<KVPair>,
+
+    /// Which operation of the query to run
+    #[arg(long, value_name = "NAME")]
+    operation: Option<String>,
+}
+
+/// A body field: `k=v` sends `v` as a string, `k:=v` sends `v` as raw JSON.
+#[derive(Debug, Clone, PartialEq)]
+struct KVPair {
+    k: String,
+    v: Value,
+}
+
+impl KVPair {
+    fn expand_env(&self, mode: ExpandMode) -> Result<Self> {
+        Ok(Self {
+            k: self.k.clone(),
+            v: expand_env_value(&self.v, mode)?,
+        })
+    }
+}
+
+/// A file uploaded as a multipart part: `field@path` or `field@path;type=MIME`.
+#[derive(Debug, Clone, PartialEq)]
+struct FileField {
+    field: String,
+    path: PathBuf,
+    mime: Option<Mime>,
+}
+
+impl FromStr for FileField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, path) = s.split_once('@').ok_or_else(|| anyhow!("Failed to parse file field {}", s))?;
+        let (path, mime) = match path.rsplit_once(";type=") {
+            Some((path, mime)) => (path, Some(mime.parse().map_err(|_| anyhow!("Invalid MIME type {} for field {}", mime, field))?)),
+            None => (path, None),
+        };
+        if field.is_empty() || path.is_empty() {
+            return Err(anyhow!("Failed to parse file field {}", s));
+        }
+        Ok(Self {
+            field: field.to_string(),
+            path: path.into(),
+            mime,
+        })
+    }
+}
+
+impl FileField {
+    /// Reads the file into a part named after it, typed by `;type=` or else its extension.
+    fn part(&self) -> Result<reqwest::multipart::Part> {
+        let content = std::fs::read(&self.path)
+            .map_err(|e| anyhow!("Failed to read {} for field {}: {}", self.path.display(), self.field, e))?;
+        let mime = match &self.mime {
+            Some(mime) => mime.clone(),
+            None => mime_guess::from_path(&self.path).first_or_octet_stream(),
+        };
+        let mut part = reqwest::multipart::Part::bytes(content).mime_str(mime.as_ref())?;
+        if let Some(name) = self.path.file_name() {
+            part = part.file_name(name.to_string_lossy().into_owned());
+        }
+        Ok(part)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BodyItem {
+    Field(KVPair),
+    File(FileField),
+}
+
+impl FromStr for BodyItem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Whichever of `@` and `=` comes first decides, so `email=a@b.c` is a field.
+        match (s.find('@'), s.find('=')) {
+            (Some(at), Some(eq)) if at < eq => Ok(Self::File(s.parse()?)),
+            (Some(_), None) => Ok(Self::File(s.parse()?)),
+            _ => Ok(Self::Field(s.parse()?)),
+        }
+    }
+}
+
+impl Post {
+    fn fields(&self) -> Vec<KVPair> {
+        self.body
+            .iter()
+            .filter_map(|item| match item {
+                BodyItem::Field(pair) => Some(pair.clone()),
+                BodyItem::File(_) => None,
+            })
+            .collect()
+    }
+
+    fn has_files(&self) -> bool {
+        self.body.iter().any(|item| matches!(item, BodyItem::File(_)))
+    }
+}
+
+impl FromStr for KVPair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!(format!("Failed to parse {}", s));
+        let (k, v) = s.split_once('=').ok_or_else(err)?;
+        Ok(match k.strip_suffix(':') {
+            Some(k) => Self {
+                k: k.to_string(),
+                v: serde_json::from_str(v).map_err(|e| anyhow!("Failed to parse {}: {}", s, e))?,
+            },
+            None => Self {
+                k: k.to_string(),
+                v: v.into(),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Filter {
+    expr: String,
+    segments: Vec<Segment>,
+}
+
+/// Parses a path like `.data.items[0].id`; the leading dot is optional.
+fn parse_path(s: &str) -> Result<Vec<Segment>> {
+    let err = || anyhow!(format!("Failed to parse path {}", s));
+    let mut segments = Vec::new();
+    let mut rest = s.strip_prefix('.').unwrap_or(s);
+    let mut first = true;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']').ok_or_else(err)?;
+            segments.push(Segment::Index(r[..end].parse().map_err(|_| err())?));
+            rest = &r[end + 1..];
+        } else {
+            let r = if first { rest } else { rest.strip_prefix('.').ok_or_else(err)? };
+            let end = r.find(['.', '[']).unwrap_or(r.len());
+            if end == 0 {
+                return Err(err());
+            }
+            segments.push(Segment::Key(r[..end].to_string()));
+            rest = &r[end..];
+        }
+        first = false;
+    }
+    Ok(segments)
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            expr: s.to_string(),
+            segments: parse_path(s).map_err(|_| anyhow!(format!("Failed to parse filter {}", s)))?,
+        })
+    }
+}
+
+impl Filter {
+    fn apply<'a>(&self, value: &'a Value) -> Result<&'a Value> {
+        self.segments.iter().try_fold(value, |v, segment| {
+            let next = match segment {
+                Segment::Key(k) => v.get(k),
+                Segment::Index(i) => v.get(*i),
+            };
+            next.ok_or_else(|| anyhow!("Filter {} does not match the response", self.expr))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Auth {
+    user: String,
+    password: Option<String>,
+}
+
+impl FromStr for Auth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (user, password) = match s.split_once(':') {
+            Some((user, password)) => (user, Some(password.to_string())),
+            None => (s, None),
+        };
+        if user.is_empty() {
+            return Err(anyhow!(format!("Failed to parse auth {}", s)));
+        }
+        Ok(Self {
+            user: user.to_string(),
+            password,
+        })
+    }
+}
+
+impl Auth {
+    /// Returns the credentials, prompting on the terminal without echo when no password was
+    /// given. Without a terminal the password is left empty.
+    fn resolve(&self) -> Result<(String, String)> {
+        let password = match &self.password {
+            Some(password) => password.clone(),
+            None => rpassword::prompt_password(format!("http: password for {}: ", self.user)).unwrap_or_else(|_| {
+                eprintln!("{} no terminal to prompt for the password of {}; sending none", "warning:".yellow(), self.user);
+                String::new()
+            }),
+        };
+        Ok((self.user.clone(), password))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HeaderPair {
+    name: HeaderName,
+    value: String,
+}
+
+impl FromStr for HeaderPair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!(format!("Failed to parse header {}", s));
+        let (name, value) = s.split_once(':').ok_or_else(err)?;
+        let value = value.trim();
+        HeaderValue::from_str(value).map_err(|_| err())?;
+        Ok(Self {
+            name: name.trim().parse().map_err(|_| err())?,
+            value: value.to_string(),
+        })
+    }
+}
+
+/// A DNS override. reqwest overrides by host alone, so it applies to every port of HOST and
+/// the port in `addr` is only kept for display; the URL decides which port is used.
+#[derive(Debug, Clone, PartialEq)]
+struct Resolve {
+    host: String,
+    addr: SocketAddr,
+}
+
+impl FromStr for Resolve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut split = s.splitn(3, ':');
+        let err = || anyhow!(format!("Failed to parse resolve {}, expected HOST:PORT:ADDR", s));
+        let (host, port, addr) = match (split.next(), split.next(), split.next()) {
+            (Some(host), Some(port), Some(addr)) if !host.is_empty() => (host, port, addr),
+            _ => return Err(err()),
+        };
+        let port: u16 = port
+            .parse()
+            .map_err(|_| anyhow!("Invalid port {} in resolve {}", port, s))?;
+        let ip: IpAddr = addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| anyhow!("Invalid IP address {} in resolve {}", addr, s))?;
+        Ok(Self {
+            host: host.to_string(),
+            addr: SocketAddr::new(ip, port),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct PrintFlags {
+    request_headers: bool,
+    request_body: bool,
+    response_headers: bool,
+    response_body: bool,
+}
+
+impl FromStr for PrintFlags {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flags = Self::default();
+        for c in s.chars() {
+            match c {
+                'H' => flags.request_headers = true,
+                'B' => flags.request_body = true,
+                'h' => flags.response_headers = true,
+                'b' => flags.response_body = true,
+                _ => return Err(anyhow!("Unknown print flag {}, expected any of HBhb", c)),
+            }
+        }
+        Ok(flags)
+    }
+}
+
+fn parse_url(s: &str) -> Result<String> {
+    // A bare path is only usable with --unix-socket, which `Context::url` checks.
+    if !s.starts_with('/') {
+        let _url: Url = Scheme::Https.apply(s).parse()?;
+    }
+
+    Ok(s.into())
+}
+
+fn parse_path_var(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((name, value)) if is_var_name(name) => Ok((name.to_string(), value.to_string())),
+        _ => Err(anyhow!("Invalid path variable {}, expected NAME=VALUE", s)),
+    }
+}
+
+fn is_var_name(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Fills in the `{name}` placeholders of a URL template. Braces around anything but a name are
+/// left alone.
+fn expand_path_vars(url: &str, vars: &[(String, String)]) -> Result<String> {
+    const SEGMENT: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+    let mut out = String::new();
+    let mut rest = url;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+        out.push_str(&rest[..start]);
+        if is_var_name(name) {
+            let (_, value) = vars
+                .iter()
+                .rev()
+                .find(|(var, _)| var == name)
+                .ok_or_else(|| anyhow!("URL {} needs {{{}}}; give it with --path-var {}=VALUE", url, name, name))?;
+            out.extend(percent_encoding::utf8_percent_encode(value, SEGMENT));
+        } else {
+            out.push_str(&rest[start..=start + len]);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn parse_kv_pair(s: &str) -> Result<KVPair> {
+    s.parse()
+}
+
+fn parse_body_item(s: &str) -> Result<BodyItem> {
+    s.parse()
+}
+
+fn parse_filter(s: &str) -> Result<Filter> {
+    s.parse()
+}
+
+fn parse_pointer(s: &str) -> Result<String> {
+    if !s.is_empty() && !s.starts_with('/') {
+        return Err(anyhow!("JSON Pointer {} must be empty or start with /", s));
+    }
+    Ok(s.into())
+}
+
+fn parse_print_flags(s: &str) -> Result<PrintFlags> {
+    s.parse()
+}
+
+fn parse_auth(s: &str) -> Result<Auth> {
+    s.parse()
+}
+
+fn parse_resolve(s: &str) -> Result<Resolve> {
+    s.parse()
+}
+
+fn parse_header(s: &str) -> Result<HeaderPair> {
+    s.parse()
+}
+
+fn parse_status_matcher(s: &str) -> Result<StatusMatcher> {
+    s.parse()
+}
+
+fn parse_range(s: &str) -> Result<ByteRange> {
+    s.parse()
+}
+
+fn parse_http_date(s: &str) -> Result<HeaderValue> {
+    httpdate::parse_http_date(s).map_err(|_| anyhow!("Failed to parse date {}, expected one like Wed, 21 Oct 2015 07:28:00 GMT", s))?;
+    Ok(HeaderValue::from_str(s)?)
+}
+
+fn parse_etag(s: &str) -> Result<HeaderValue> {
+    let quoted = s == "*" || s.starts_with('"') || s.starts_with("W/");
+    let etag = if quoted { s.to_string() } else { format!("\"{}\"", s) };
+    HeaderValue::from_str(&etag).map_err(|_| anyhow!("Invalid ETag {}", s))
+}
+
+/// Expands a MIME shorthand such as `json`; anything else is returned as is.
+fn expand_mime(s: &str) -> &str {
+    match s.to_ascii_lowercase().as_str() {
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "form" => "application/x-www-form-urlencoded",
+        "html" => "text/html",
+        "text" => "text/plain",
+        "csv" => "text/csv",
+        "yaml" => "application/yaml",
+        "ndjson" => "application/x-ndjson",
+        "binary" => "application/octet-stream",
+        _ => s,
+    }
+}
+
+fn parse_accept(s: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(expand_mime(s)).map_err(|_| anyhow!("Invalid Accept value {}", s))
+}
+
+fn parse_mime(s: &str) -> Result<Mime> {
+    expand_mime(s).parse().map_err(|_| anyhow!("Invalid MIME type {}", s))
+}
+
+fn parse_seconds(s: &str) -> Result<Duration> {
+    let secs: f64 = s.parse().map_err(|_| anyhow!("Invalid number of seconds {}", s))?;
+    Duration::try_from_secs_f64(secs).map_err(|e| anyhow!("Invalid number of seconds {}: {}", s, e))
+}
+
+/// A rate in requests a second, returned as the interval between request starts.
+fn parse_rps(s: &str) -> Result<Duration> {
+    let err = || anyhow!("Invalid rate {}, expected a positive number of requests a second", s);
+    match s.parse::<f64>() {
+        Ok(rps) if rps > 0.0 && rps.is_finite() => Duration::try_from_secs_f64(1.0 / rps).map_err(|_| err()),
+        _ => Err(err()),
+    }
+}
+
+/// A byte count with an optional binary `k`, `m` or `g` suffix.
+fn parse_rate(s: &str) -> Result<u64> {
+    let err = || anyhow!("Invalid rate {}, expected bytes a second like 2048, 500k or 2m", s);
+    let (digits, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_lowercase()),
+        _ => (s, 'b'),
+    };
+    let multiplier: u64 = match unit {
+        'b' => 1,
+        'k' => 1 << 10,
+        'm' => 1 << 20,
+        'g' => 1 << 30,
+        _ => return Err(err()),
+    };
+    let rate = digits.parse::<u64>().map_err(|_| err())?.checked_mul(multiplier).ok_or_else(err)?;
+    if rate == 0 {
+        return Err(err());
+    }
+    Ok(rate)
+}
+
+fn parse_grep(s: &str) -> Result<Regex> {
+    Regex::new(s).map_err(|e| anyhow!("Invalid --grep pattern: {}", e))
+}
+
+/// Replaces `$NAME` and `${NAME}` with environment variables; `$$` stays a literal `$`.
+fn expand_env(s: &str, mode: ExpandMode) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        if let Some(r) = after.strip_prefix('$') {
+            out.push('$');
+            rest = r;
+            continue;
+        }
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        match (std::env::var(name), mode) {
+            (Ok(value), _) => out.push_str(&value),
+            (Err(_), ExpandMode::Lenient) => out.push_str(&rest[i..i + 1 + len]),
+            (Err(_), ExpandMode::Empty) => eprintln!("{} environment variable {} is not set", "warning:".yellow(), name),
+            (Err(_), ExpandMode::Strict) => return Err(anyhow!("Environment variable {} is not set", name)),
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Expands text values and the strings inside raw JSON ones.
+fn expand_env_value(value: &Value, mode: ExpandMode) -> Result<Value> {
+    Ok(match value {
+        Value::String(s) => Value::String(expand_env(s, mode)?),
+        Value::Array(items) => Value::Array(items.iter().map(|v| expand_env_value(v, mode)).collect::<Result<_>>()?),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), expand_env_value(v, mode)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// The largest array index a body key may use, since the array is filled with nulls up to it.
+const MAX_BODY_INDEX: usize = 1024;
+
+/// Stores `value` at `path` inside `target`, creating objects and arrays on the way.
+fn insert_path(target: &mut Value, path: &[Segment], value: Value, key: &str) -> Result<()> {
+    let conflict = || anyhow!("Conflicting body key {}: a value and nested fields share the same path", key);
+    let (segment, rest) = match path.split_first() {
+        Some(v) => v,
+        None => {
+            if target.is_object() || target.is_array() {
+                return Err(conflict());
+            }
+            *target = value;
+            return Ok(());
+        }
+    };
+    let slot = match segment {
+        Segment::Key(k) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let object = target.as_object_mut().ok_or_else(conflict)?;
+            object.entry(k.clone()).or_insert(Value::Null)
+        }
+        Segment::Index(i) => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let array = target.as_array_mut().ok_or_else(conflict)?;
+            if *i > MAX_BODY_INDEX {
+                return Err(anyhow!("Body key {} has index {}, more than the {} allowed", key, i, MAX_BODY_INDEX));
+            }
+            if array.len() <= *i {
+                array.resize(i + 1, Value::Null);
+            }
+            &mut array[*i]
+        }
+    };
+    insert_path(slot, rest, value, key)
+}
+
+/// Assembles the JSON body; a key given more than once collects its values into an array.
+fn build_body(pairs: &[KVPair]) -> Result<Value> {
+    let mut fields: Vec<(Vec<Segment>, &str, Vec<Value>)> = Vec::new();
+    for pair in pairs {
+        let path = parse_path(&pair.k).map_err(|_| anyhow!("Failed to parse body key {}", pair.k))?;
+        if path.is_empty() {
+            return Err(anyhow!("Body key must not be empty"));
+        }
+        match fields.iter_mut().find(|(p, _, _)| *p == path) {
+            Some((_, _, values)) => values.push(pair.v.clone()),
+            None => fields.push((path, &pair.k, vec![pair.v.clone()])),
+        }
+    }
+
+    let mut body = Value::Object(Map::new());
+    for (path, key, mut values) in fields {
+        let value = if values.len() == 1 { values.remove(0) } else { Value::Array(values) };
+        insert_path(&mut body, &path, value, key)?;
+    }
+    Ok(body)
+}
+
+/// Shared state for running a subcommand.
+struct Context<'a> {
+    client: Client,
+    /// Headers the client adds to every request, kept so they can be printed.
+    headers: HeaderMap,
+    /// Headers from `-H`, which replace any the request already carries.
+    overrides: HeaderMap,
+    /// Auth `(user, password)` with any prompting already done.
+    auth: Option<(String, String)>,
+    /// Credentials looked up by host when `auth` is unset.
+    netrc: Option<netrc::Netrc>,
+    /// Where GET responses are cached, when they are.
+    cache: Option<PathBuf>,
+    /// Headers from the config sent to hosts matching a pattern, unless the request has them.
+    host_headers: Vec<(String, HeaderMap)>,
+    cli: &'a Cli,
+}
+
+/// Keeps fields in order and repeated keys as separate fields, unlike the JSON body.
+fn build_form(pairs: &[KVPair]) -> Result<Vec<(&str, &str)>> {
+    pairs
+        .iter()
+        .map(|pair| match &pair.v {
+            Value::String(v) => Ok((pair.k.as_str(), v.as_str())),
+            _ => Err(anyhow!("Form field {} must be a string, not raw JSON", pair.k)),
+        })
+        .collect()
+}
+
+/// A multipart form with the text fields and files in the order they were given.
+fn build_multipart(ctx: &Context<'_>, items: &[BodyItem]) -> Result<reqwest::multipart::Form> {
+    let mut form = reqwest::multipart::Form::new();
+    for item in items {
+        form = match item {
+            BodyItem::Field(pair) => {
+                let pairs = ctx.pairs(std::slice::from_ref(pair))?;
+                let (k, v) = build_form(&pairs)?[0];
+                form.text(k.to_string(), v.to_string())
+            }
+            BodyItem::File(file) => form.part(file.field.clone(), file.part()?),
+        };
+    }
+    Ok(form)
+}
+
+impl Context<'_> {
+    fn pairs(&self, pairs: &[KVPair]) -> Result<Vec<KVPair>> {
+        match self.cli.expand_mode() {
+            Some(mode) => pairs.iter().map(|pair| pair.expand_env(mode)).collect(),
+            None => Ok(pairs.to_vec()),
+        }
+    }
+
+    /// The credentials for `url`: those given with --auth, or else the netrc ones for its host.
+    fn credentials(&self, url: &Url) -> Option<(String, String)> {
+        self.auth.clone().or_else(|| self.netrc.as_ref()?.credentials(url.host_str()?))
+    }
+
+    fn url(&self, url: &str) -> Result<Url> {
+        let url = &expand_path_vars(url, &self.cli.path_var)?;
+        match self.cli.unix_socket {
+            Some(_) if url.starts_with('/') => Ok(format!("http://localhost{}", url).parse()?),
+            None if url.starts_with('/') => Err(anyhow!("URL {} has no host; bare paths need --unix-socket", url)),
+            _ => Ok(self.cli.default_scheme.apply(url).parse()?),
+        }
+    }
+}
+
+/// Sends the GET, returning the response for a single URL; several URLs are each checked
+/// against --expect-status as they're printed.
+async fn get(ctx: &Context<'_>, args: &Get) -> Result<Option<ResponseSummary>> {
+    if let [url] = args.url.as_slice() {
+        return send(ctx, ctx.client.get(ctx.url(url)?)).await;
+    }
+    if ctx.cli.output.is_some() {
+        return Err(anyhow!("--output can't be used with several URLs; try --download"));
+    }
+    if ctx.cli.repeat.is_some() {
+        return Err(anyhow!("--repeat takes a single URL"));
+    }
+
+    let limit = ctx.cli.concurrency.map_or(args.url.len(), NonZeroUsize::get);
+    let limiter = ctx.cli.rate_limit.map(RateLimiter::new);
+    let host_of = |url: &str| Some(ctx.url(url).ok()?.host_str()?.to_string());
+    let per_host: HashMap<_, _> = match ctx.cli.max_per_host {
+        Some(n) => args.url.iter().filter_map(|url| host_of(url)).map(|host| (host, Semaphore::new(n.get()))).collect(),
+        None => HashMap::new(),
+    };
+    let mut results = stream::iter(&args.url)
+        .map(|url| async {
+            let _permit = match host_of(url).and_then(|host| per_host.get(&host)) {
+                Some(semaphore) => Some(semaphore.acquire().await?),
+                None => None,
+            };
+            if let Some(limiter) = &limiter {
+                limiter.acquire().await;
+            }
+            fetch(ctx, url).await
+        })
+        .buffered(limit);
+
+    let mut failed = 0;
+    let mut urls = args.url.iter().enumerate();
+    while let Some(result) = results.next().await {
+        let (i, url) = urls.next().unwrap();
+        if !ctx.cli.quiet {
+            if i > 0 {
+                println!();
+            }
+            println!("{}\n", format!("==> {}", url).bold());
+        }
+        let printed = match result {
+            Ok((request, response)) => {
+                if let Some(request) = request {
+                    print_request(&request, ctx.cli)?;
+                }
+                print_resp(response, ctx.cli, None).await.and_then(|summary| check_status(ctx.cli, summary.status))
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = printed {
+            failed += 1;
+            eprintln!("{} {}\n", "error:".red(), describe_error(&err).unwrap_or_else(|| format!("{:#}", err)));
+        }
+    }
+    match failed {
+        0 => Ok(None),
+        n => Err(anyhow!("{} of {} requests failed", n, args.url.len())),
+    }
+}
+
+/// Sends a GET and buffers the response, keeping a copy of the request for printing.
+async fn fetch(ctx: &Context<'_>, url: &str) -> Result<(Option<Request>, Response)> {
+    let request = build_request(ctx, ctx.client.get(ctx.url(url)?))?;
+    let printable = request.try_clone();
+    let response = exchange(ctx, request).await?;
+    Ok((printable, buffer_response(response).await?))
+}
+
+/// Reads the whole body so the response can be printed after others have finished.
+async fn buffer_response(resp: Response) -> Result<Response> {
+    Ok(read_response(resp).await?.0)
+}
+
+/// Reads the whole body, returning it along with a response that still carries it.
+async fn read_response(resp: Response) -> Result<(Response, Vec<u8>)> {
+    let mut builder = http::Response::builder()
+        .status(resp.status())
+        .version(resp.version())
+        .url(resp.url().clone());
+    *builder.headers_mut().unwrap() = resp.headers().clone();
+    let body = resp.bytes().await?;
+    let bytes = body.to_vec();
+    Ok((builder.body(body)?.into(), bytes))
+}
+
+/// Appends the exchange to the `--log` file, handing back the response for printing.
+async fn log_exchange(path: &Path, request: Option<&Request>, resp: Response) -> Result<Response> {
+    let (resp, body) = read_response(resp).await?;
+    exchange_log::append(path, &exchange_log::record(SystemTime::now(), request, &resp, &body))?;
+    Ok(resp)
+}
+
+/// Reads the raw body given by `--raw`, `--body-env` or `--body-file`, or a piped stdin when
+/// there's no other body source.
+fn read_raw_body(ctx: &Context<'_>, args: &Post) -> Result<Option<Vec<u8>>> {
+    if let Some(body) = &args.raw {
+        return Ok(Some(body.clone().into_bytes()));
+    }
+    if let Some(name) = &args.body_env {
+        let body = std::env::var(name).map_err(|_| anyhow!("Environment variable {} is not set", name))?;
+        return Ok(Some(body.into_bytes()));
+    }
+    if args.body_clipboard {
+        return read_clipboard().map(|body| Some(body.into_bytes()));
+    }
+    let read_stdin = || -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        io::stdin().read_to_end(&mut body)?;
+        Ok(body)
+    };
+    match &args.body_file {
+        Some(path) if path.as_os_str() == "-" => {
+            if ctx.cli.ignore_stdin {
+                return Err(anyhow!("--body-file - reads stdin, which --ignore-stdin forbids"));
+            }
+            Ok(Some(read_stdin()?))
+        }
+        Some(path) => std::fs::read(path)
+            .map(Some)
+            .map_err(|e| anyhow!("Failed to read body file {}: {}", path.display(), e)),
+        None if args.body.is_empty() && !args.form && !args.multipart && !ctx.cli.ignore_stdin && !io::stdin().is_terminal() => {
+            let body = read_stdin()?;
+            Ok(if body.is_empty() { None } else { Some(body) })
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn read_clipboard() -> Result<String> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|e| anyhow!("Failed to read the clipboard: {}", e))?;
+    if text.is_empty() {
+        return Err(anyhow!("The clipboard is empty"));
+    }
+    Ok(text)
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn read_clipboard() -> Result<String> {
+    Err(anyhow!("--body-clipboard needs httpie built with the clipboard feature"))
+}
+
+async fn post(ctx: &Context<'_>, method: Method, args: &Post) -> Result<Option<ResponseSummary>> {
+    send(ctx, post_request(ctx, method, args)?).await
+}
+
+fn post_request(ctx: &Context<'_>, method: Method, args: &Post) -> Result<RequestBuilder> {
+    if args.form && ctx.cli.json {
+        return Err(anyhow!("--json and --form cannot be used together"));
+    }
+    if args.has_files() && args.content_type.is_some() {
+        return Err(anyhow!("--content-type can't be used with file fields, which are sent as multipart"));
+    }
+    let builder = ctx.client.request(method, ctx.url(&args.url)?);
+    let content_type = args.content_type.as_ref().unwrap_or(&APPLICATION_JSON);
+    let builder = if let Some(body) = read_raw_body(ctx, args)? {
+        builder.header(header::CONTENT_TYPE, content_type.as_ref()).body(body)
+    } else if args.multipart || args.has_files() {
+        builder.multipart(build_multipart(ctx, &args.body)?)
+    } else if args.form {
+        builder.form(&build_form(&ctx.pairs(&args.fields())?)?)
+    } else if args.body.is_empty() {
+        // No fields means no body, rather than `{}`.
+        builder
+    } else {
+        let body = build_body(&ctx.pairs(&args.fields())?)?;
+        builder
+            .header(header::CONTENT_TYPE, content_type.as_ref())
+            .body(serde_json::to_vec(&body)?)
+    };
+    Ok(builder)
+}
+
+/// The file of the --session or --session-read-only session for requests to `url`.
+fn session_path(cli: &Cli, url: &Url) -> Result<Option<PathBuf>> {
+    let Some(name) = cli.session.as_ref().or(cli.session_read_only.as_ref()) else {
+        return Ok(None);
+    };
+    let host = match url.port() {
+        Some(port) => format!("{}_{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    session::path(name, &host).map(Some).ok_or_else(|| anyhow!("Can't find the sessions directory without HOME"))
+}
+
+/// Keeps the request's -H headers and the response's cookies in the --session, unless it's
+/// read-only.
+fn update_session(ctx: &Context<'_>, url: &Url, response: &Response) -> Result<()> {
+    if ctx.cli.session.is_none() {
+        return Ok(());
+    }
+    let Some(path) = session_path(ctx.cli, url)? else {
+        return Ok(());
+    };
+    let mut session = session::load(&path)?;
+    session.update(&ctx.overrides, response.headers());
+    session::save(&path, &session)
+}
+
+/// A token for the --oauth2 flags: the one kept in the session while it's still valid, or
+/// else a new one, kept for next time unless the session is read-only. The session is the one
+/// for the token endpoint's host, as the token doesn't depend on the URL it's sent to.
+async fn oauth2_token(client: &Client, cli: &Cli, url: &str, id: &str, secret: &str) -> Result<String> {
+    let scope = cli.oauth2_scope.as_deref();
+    let token_url = Url::parse(url).map_err(|e| anyhow!("Invalid --oauth2-token-url {}: {}", url, e))?;
+    let path = session_path(cli, &token_url)?;
+    let mut session = path.as_deref().map(session::load).transpose()?;
+    let kept = session.as_ref().and_then(|session| session.oauth2_token.as_ref());
+    if let Some(token) = kept.filter(|token| token.is_valid_for(url, id, scope, SystemTime::now())) {
+        return Ok(token.access_token.clone());
+    }
+    let token = oauth2::fetch_token(client, url, id, secret, scope).await?;
+    let access_token = token.access_token.clone();
+    if let (Some(path), Some(session)) = (path.filter(|_| cli.session.is_some()), session.as_mut()) {
+        if token.expires_at.is_some() {
+            session.oauth2_token = Some(token);
+            session::save(&path, session)?;
+        }
+    }
+    Ok(access_token)
+}
+
+const X_REQUEST_ID: &str = "x-request-id";
+
+/// With --request-id, reports whether the response echoed the ID that was sent.
+fn check_request_id(cli: &Cli, sent: Option<&HeaderValue>, resp: &Response) {
+    let (Some(sent), Some(echoed)) = (sent.filter(|_| cli.request_id), resp.headers().get(X_REQUEST_ID)) else {
+        return;
+    };
+    if sent == echoed {
+        eprintln!("{} {}", "request id:".dimmed(), "echoed back".green());
+    } else {
+        eprintln!("{} the response has X-Request-ID {}, not the one sent", "warning:".yellow(), format_header_value(echoed));
+    }
+}
+
+/// Applies the per-run options to `builder` and fills in the client's default headers.
+fn build_request(ctx: &Context<'_>, builder: RequestBuilder) -> Result<Request> {
+    let (client, request) = builder.build_split();
+    let mut request = request?;
+    // reqwest has already moved `user:password@` out of the URL into an Authorization header,
+    // which --auth replaces and netrc leaves alone.
+    if ctx.auth.is_some() {
+        request.headers_mut().remove(header::AUTHORIZATION);
+    }
+    let basic = ctx.cli.auth_type == AuthType::Basic && !request.headers().contains_key(header::AUTHORIZATION);
+    let auth = ctx.credentials(request.url()).filter(|_| basic);
+    let builder = RequestBuilder::from_parts(client, request);
+    let builder = match auth {
+        Some((user, password)) => builder.basic_auth(user, Some(password)),
+        None => builder,
+    };
+    // hyper only fills in Host from the URL when the request doesn't carry one.
+    let builder = match &ctx.cli.host_header {
+        Some(host) => builder.header(header::HOST, host),
+        None => builder,
+    };
+    let builder = match ctx.cli.range {
+        Some(range) => builder.header(header::RANGE, range.to_string()),
+        None => builder,
+    };
+    let builder = match &ctx.cli.if_modified_since {
+        Some(date) => builder.header(header::IF_MODIFIED_SINCE, date),
+        None => builder,
+    };
+    let builder = match &ctx.cli.if_none_match {
+        Some(etag) => builder.header(header::IF_NONE_MATCH, etag),
+        None => builder,
+    };
+    let mut request = builder.build()?;
+    for name in ctx.overrides.keys() {
+        request.headers_mut().remove(name);
+    }
+    for (name, value) in &ctx.overrides {
+        request.headers_mut().append(name, value.clone());
+    }
+    if let Some(path) = session_path(ctx.cli, request.url())? {
+        session::load(&path)?.apply(request.headers_mut())?;
+    }
+    let host = request.url().host_str().unwrap_or_default().to_string();
+    for (_, headers) in ctx.host_headers.iter().filter(|(pattern, _)| config::host_matches(pattern, &host)) {
+        for (name, value) in headers {
+            if !request.headers().contains_key(name) {
+                request.headers_mut().insert(name, value.clone());
+            }
+        }
+    }
+    for name in ctx.headers.keys() {
+        if !request.headers().contains_key(name) {
+            for value in ctx.headers.get_all(name) {
+                request.headers_mut().append(name, value.clone());
+            }
+        }
+    }
+    if ctx.cli.request_id {
+        let id = match request.headers().get(X_REQUEST_ID) {
+            Some(id) => format_header_value(id),
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                request.headers_mut().insert(X_REQUEST_ID, id.parse()?);
+                id
+            }
+        };
+        eprintln!("{} {}", "request id:".dimmed(), id);
+    }
+    if let Some(mode) = ctx.cli.compress {
+        compress_body(&mut request, mode, ctx.cli.compress_with)?;
+    }
+    if ctx.cli.show_url {
+        eprintln!("{} {}", "url:".dimmed(), request.url());
+    }
+    Ok(request)
+}
+
+/// Compresses the body in place and sets Content-Encoding. Streaming bodies and ones that are
+/// already encoded are left alone.
+fn compress_body(request: &mut Request, mode: CompressMode, coding: Coding) -> Result<()> {
+    if request.headers().contains_key(header::CONTENT_ENCODING) {
+        return Ok(());
+    }
+    let Some(bytes) = request.body().and_then(|b| b.as_bytes()) else {
+        return Ok(());
+    };
+    if mode == CompressMode::Auto && bytes.len() <= COMPRESS_THRESHOLD {
+        return Ok(());
+    }
+    let compressed = encoding::encode(coding.as_str(), bytes)?;
+    request.headers_mut().insert(header::CONTENT_ENCODING, HeaderValue::from_static(coding.as_str()));
+    request.headers_mut().insert(header::CONTENT_LENGTH, compressed.len().into());
+    *request.body_mut() = Some(compressed.into());
+    Ok(())
+}
+
+/// The request body as text, or a note about its size when it's compressed.
+fn request_body_text(req: &Request) -> Option<(String, bool)> {
+    let bytes = req.body()?.as_bytes()?;
+    Some(match req.headers().get(header::CONTENT_ENCODING) {
+        Some(encoding) => (format!("[{} bytes, {}-encoded]", bytes.len(), format_header_value(encoding)), false),
+        None => (String::from_utf8_lossy(bytes).into_owned(), true),
+    })
+}
+
+/// Sends the request and prints the response, returning it unless --repeat sent it many times.
+async fn send(ctx: &Context<'_>, builder: RequestBuilder) -> Result<Option<ResponseSummary>> {
+    send_with(ctx, builder, false).await
+}
+
+/// Like `send`, but with `keep_body` the summary carries the decoded body even when the
+/// response isn't printed.
+async fn send_with(ctx: &Context<'_>, builder: RequestBuilder, keep_body: bool) -> Result<Option<ResponseSummary>> {
+    let mut request = build_request(ctx, builder)?;
+    if let Some(n) = ctx.cli.repeat {
+        return benchmark(ctx, request, n.get()).await.map(|_| None);
+    }
+    let resume = resume_range(ctx.cli, request.url())?;
+    if let Some((_, range)) = &resume {
+        request.headers_mut().insert(header::RANGE, range.to_string().parse()?);
+    }
+    print_request(&request, ctx.cli)?;
+    let started = Instant::now();
+    let mut response = exchange(ctx, request).await?;
+    let mut body = None;
+    if keep_body {
+        let (buffered, raw) = read_response(response).await?;
+        body = Some(client::decode_body(buffered.headers(), raw)?);
+        response = buffered;
+    }
+    let mut summary = print_resp(response, ctx.cli, resume.as_ref().map(|(path, _)| path.as_path())).await?;
+    if let Some(body) = body {
+        summary.body = body;
+    }
+    Ok(Some(ResponseSummary { elapsed: started.elapsed(), ..summary }))
+}
+
+/// Executes `request`, answering a digest challenge, checking the echoed request ID and
+/// updating the --session, and appends the exchange to the `--log` file if there is one.
+async fn exchange(ctx: &Context<'_>, request: Request) -> Result<Response> {
+    let request_id = request.headers().get(X_REQUEST_ID).cloned();
+    let url = request.url().clone();
+    let mut printable = request.try_clone();
+    let mut response = execute_cached(ctx, request).await?;
+    if let Some(retry) = digest_retry(ctx, printable.as_ref(), &response)? {
+        printable = retry.try_clone();
+        response = execute(ctx, retry).await?;
+    }
+    check_request_id(ctx.cli, request_id.as_ref(), &response);
+    update_session(ctx, &url, &response)?;
+    match &ctx.cli.log {
+        Some(path) => log_exchange(path, printable.as_ref(), response).await,
+        None => Ok(response),
+    }
+}
+
+/// Executes `request`, answering a GET from the --cache when the stored copy is fresh, and
+/// revalidating it with its ETag when it's stale. Requests with credentials or cookies skip
+/// the cache, since its entries are shared by everyone asking for the same URL.
+async fn execute_cached(ctx: &Context<'_>, mut request: Request) -> Result<Response> {
+    let private = [header::AUTHORIZATION, header::COOKIE]
+        .iter()
+        .any(|name| request.headers().contains_key(name) || ctx.headers.contains_key(name));
+    let Some(dir) = ctx.cache.as_deref().filter(|_| request.method() == Method::GET && !private) else {
+        return execute(ctx, request).await;
+    };
+    let url = request.url().to_string();
+    let cached = cache::load(dir, &url);
+    if let Some(entry) = cached.as_ref().filter(|entry| entry.is_fresh(SystemTime::now())) {
+        return cached_response(request.url(), entry);
+    }
+    // A 304 to the user's own If-None-Match is theirs to see, not a cue to use the cache.
+    let cached = cached.filter(|_| !request.headers().contains_key(header::IF_NONE_MATCH));
+    if let Some(etag) = cached.as_ref().and_then(cache::Entry::etag) {
+        request.headers_mut().insert(header::IF_NONE_MATCH, etag.parse()?);
+    }
+    let response = execute(ctx, request).await?;
+    match (response.status(), cached) {
+        (StatusCode::NOT_MODIFIED, Some(entry)) => {
+            let entry = entry.revalidated(response.headers(), SystemTime::now());
+            cache::store(dir, &entry)?;
+            cached_response(response.url(), &entry)
+        }
+        (StatusCode::OK, _) => match cache::Entry::new(&url, 200, response.headers(), Vec::new(), SystemTime::now()) {
+            Some(entry) => cache_while_reading(cache::Writer::create(dir, entry)?, response),
+            None => Ok(response),
+        },
+        (_, _) => Ok(response),
+    }
+}
+
+/// Hands back `response` with its body going into the cache as it's read, so it can still be
+/// streamed; the entry is stored once the last chunk has gone through.
+fn cache_while_reading(writer: cache::Writer, response: Response) -> Result<Response> {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    *builder.headers_mut().unwrap() = response.headers().clone();
+    let chunks = stream::unfold((response.bytes_stream(), Some(writer)), |(mut chunks, mut writer)| async move {
+        match chunks.next().await {
+            Some(Ok(chunk)) => {
+                if let Some(Err(err)) = writer.as_mut().map(|writer| writer.write(&chunk)) {
+                    eprintln!("{} {}", "warning:".yellow(), err);
+                    writer = None;
+                }
+                Some((Ok(chunk), (chunks, writer)))
+            }
+            // Dropping the writer throws away what it has so far.
+            Some(Err(err)) => Some((Err(err), (chunks, None))),
+            None => {
+                if let Some(Err(err)) = writer.map(cache::Writer::finish) {
+                    eprintln!("{} {}", "warning:".yellow(), err);
+                }
+                None
+            }
+        }
+    });
+    Ok(builder.body(reqwest::Body::wrap_stream(chunks))?.into())
+}
+
+/// A response carrying a cached body, as if it had come from `url`.
+fn cached_response(url: &Url, entry: &cache::Entry) -> Result<Response> {
+    let mut builder = http::Response::builder().status(entry.status).url(url.clone());
+    for (name, value) in &entry.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    Ok(builder.body(entry.body.clone())?.into())
+}
+
+/// With --auth-type digest, answers a 401 Digest challenge with a copy of `request` that
+/// carries the credentials.
+fn digest_retry(ctx: &Context<'_>, request: Option<&Request>, response: &Response) -> Result<Option<Request>> {
+    if ctx.cli.auth_type != AuthType::Digest || response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(None);
+    }
+    let (Some(request), Some((user, password))) = (request, ctx.credentials(response.url())) else {
+        return Ok(None);
+    };
+    let challenge = response
+        .headers()
+        .get_all(header::WWW_AUTHENTICATE)
+        .iter()
+        .find_map(|value| digest::Challenge::parse(&format_header_value(value)));
+    let Some(challenge) = challenge.transpose()? else {
+        return Ok(None);
+    };
+    let url = request.url();
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let mut retry = request.try_clone().ok_or_else(|| anyhow!("Can't resend a streamed body for Digest auth"))?;
+    let value = challenge.authorization(&user, &password, request.method().as_str(), &uri);
+    retry.headers_mut().insert(header::AUTHORIZATION, value.parse()?);
+    Ok(Some(retry))
+}
+
+async fn import_curl(ctx: &Context<'_>, args: &ImportCurl) -> Result<Option<ResponseSummary>> {
+    let (request, warnings) = curl::parse(&curl::tokenize(&args.command)?)?;
+    for warning in warnings {
+        eprintln!("{} {}", "warning:".yellow(), warning);
+    }
+    if args.show {
+        println!("{}", request.to_command()?);
+        return Ok(None);
+    }
+    let method: Method = request.method().parse()?;
+    let mut builder = ctx.client.request(method, ctx.url(&request.url)?);
+    for (name, value) in &request.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    if let Some(user) = &request.user {
+        let (user, password) = parse_auth(user)?.resolve()?;
+        builder = builder.basic_auth(user, Some(password));
+    }
+    if let Some(data) = &request.data {
+        // What curl sends for -d without a Content-Type of its own.
+        if !request.has_header("content-type") {
+            builder = builder.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        }
+        builder = builder.body(data.clone());
+    }
+    send(ctx, builder).await
+}
+
+/// The standard `{"query", "variables", "operationName"}` request body.
+fn graphql_envelope(ctx: &Context<'_>, args: &Graphql) -> Result<Value> {
+    let query = match args.query.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read query file {}: {}", path, e))?,
+        None => args.query.clone(),
+    };
+    let mut envelope = Map::new();
+    envelope.insert("query".into(), query.into());
+    envelope.insert("variables".into(), build_body(&ctx.pairs(&args.variables)?)?);
+    if let Some(name) = &args.operation {
+        envelope.insert("operationName".into(), name.clone().into());
+    }
+    Ok(Value::Object(envelope))
+}
+
+async fn graphql(ctx: &Context<'_>, args: &Graphql) -> Result<Option<ResponseSummary>> {
+    let builder = ctx
+        .client
+        .post(ctx.url(&args.url)?)
+        .header(header::CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(serde_json::to_vec(&graphql_envelope(ctx, args)?)?);
+    let summary = send_with(ctx, builder, true).await?;
+    for error in summary.iter().flat_map(|summary| graphql_errors(&summary.body)) {
+        eprintln!("{} {}", "GraphQL error:".red().bold(), error.red());
+    }
+    Ok(summary)
+}
+
+async fn options(ctx: &Context<'_>, args: &Options) -> Result<Option<ResponseSummary>> {
+    let summary = send(ctx, ctx.client.request(Method::OPTIONS, ctx.url(&args.url)?)).await?;
+    if let Some(summary) = summary.as_ref().filter(|_| !ctx.cli.quiet && !ctx.cli.output_format.is_json()) {
+        println!();
+        for line in options_summary(&summary.headers) {
+            println!("{}", line);
+        }
+    }
+    Ok(summary)
+}
+
+/// `Allow` and the `Access-Control-*` headers as lists, one line per header.
+fn options_summary(headers: &HeaderMap) -> Vec<String> {
+    let list = |name: &HeaderName| {
+        let values = headers.get_all(name).iter().map(format_header_value);
+        values.flat_map(|value| value.split(',').map(|v| v.trim().to_string()).collect::<Vec<_>>()).filter(|v| !v.is_empty()).collect::<Vec<_>>().join(", ")
+    };
+    let mut lines = Vec::new();
+    if headers.contains_key(header::ALLOW) {
+        lines.push(format!("{} {}", "Allowed methods:".bold(), list(&header::ALLOW)));
+    }
+    let mut cors: Vec<_> = headers.keys().filter(|name| name.as_str().starts_with("access-control-")).collect();
+    cors.sort_by_key(|name| name.as_str());
+    if !cors.is_empty() {
+        lines.push("CORS:".bold().to_string());
+        for name in cors {
+            let label = name.as_str().trim_start_matches("access-control-").replace('-', " ");
+            lines.push(format!("  {}: {}", label, list(name)));
+        }
+    }
+    if lines.is_empty() {
+        lines.push("No Allow or Access-Control-* headers in the response".dimmed().to_string());
+    }
+    lines
+}
+
+/// Runs a WebSocket session. The handshake carries the same auth and headers as any request.
+async fn ws(ctx: &Context<'_>, args: &Ws) -> Result<()> {
+    let request = build_request(ctx, ctx.client.get(ctx.url(&args.url)?))?;
+    print_request(&request, ctx.cli)?;
+    let (mut socket, response) = ws::connect(&request).await?;
+    if ctx.cli.print_flags().response_headers {
+        print_status(&response, ctx.cli.output_format);
+        print_header(response.headers(), ctx.cli, "< ");
+    }
+    let mut messages = args.message.clone();
+    if messages.is_empty() && !ctx.cli.ignore_stdin && !io::stdin().is_terminal() {
+        messages = io::stdin().lines().collect::<io::Result<_>>()?;
+    }
+    for message in messages {
+        socket.send(ws::Message::Text(message)).await?;
+    }
+    while let Some(message) = socket.next().await {
+        let message = message?;
+        if let Some(text) = ws::describe(&message) {
+            match message {
+                ws::Message::Text(_) => println!("{}", text),
+                _ => println!("{}", text.dimmed()),
+            }
+        }
+        if message.is_close() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn diff(ctx: &Context<'_>, args: &Diff) -> Result<()> {
+    let side = |url| async move {
+        let (_, response) = fetch(ctx, url).await?;
+        diff_text(response, args.diff_headers).await
+    };
+    let (left, right) = tokio::try_join!(side(&args.left), side(&args.right))?;
+    match unified_diff(&left, &right, &args.left, &args.right) {
+        Some(diff) => {
+            print!("{}", diff);
+            Err(anyhow!("The responses differ"))
+        }
+        None => Ok(()),
+    }
+}
+
+/// What `diff` compares of a response: its body, with JSON pretty-printed and sorted so key
+/// order doesn't count, after the status line and sorted headers if asked for.
+async fn diff_text(response: Response, headers: bool) -> Result<String> {
+    let mut text = String::new();
+    if headers {
+        text.push_str(&format!("{:?} {}\n", response.version(), response.status()));
+        let mut lines: Vec<_> = response.headers().iter().map(|(name, value)| format!("{}: {}\n", name, format_header_value(value))).collect();
+        lines.sort();
+        text.extend(lines);
+        text.push('\n');
+    }
+    let mime = get_content_type(response.headers());
+    let body = response.text().await?;
+    let format = JsonFormat { sorted: true, ..Default::default() };
+    let json = is_json(mime.as_ref()).then(|| render_json(&body, format).ok()).flatten();
+    text.push_str(json.as_deref().unwrap_or(&body));
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// A colored unified diff of `left` against `right`, or `None` when they're the same.
+fn unified_diff(left: &str, right: &str, left_name: &str, right_name: &str) -> Option<String> {
+    if left == right {
+        return None;
+    }
+    let mut out = format!("{}\n{}\n", format!("--- {}", left_name).bold(), format!("+++ {}", right_name).bold());
+    let diff = TextDiff::from_lines(left, right);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        out.push_str(&format!("{}\n", hunk.header().to_string().cyan()));
+        for change in hunk.iter_changes() {
+            let line = change.value().trim_end_matches('\n');
+            let line = match change.tag() {
+                ChangeTag::Delete => format!("-{}", line).red().to_string(),
+                ChangeTag::Insert => format!("+{}", line).green().to_string(),
+                ChangeTag::Equal => format!(" {}", line),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+/// The messages of the `errors` a GraphQL response carries, each with the path it's about.
+fn graphql_errors(body: &[u8]) -> Vec<String> {
+    let Ok(Value::Object(response)) = serde_json::from_slice(body) else {
+        return Vec::new();
+    };
+    let Some(Value::Array(errors)) = response.get("errors") else {
+        return Vec::new();
+    };
+    errors
+        .iter()
+        .map(|error| {
+            let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+            match error.get("path").and_then(Value::as_array) {
+                Some(path) => {
+                    let path: Vec<_> = path.iter().map(|p| p.to_string().trim_matches('"').to_string()).collect();
+                    format!("{} (at {})", message, path.join("."))
+                }
+                None => message.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// With --range and a target file that already exists, returns the file and the part of the
+/// range still missing from it.
+fn resume_range(cli: &Cli, url: &Url) -> Result<Option<(PathBuf, ByteRange)>> {
+    let path = cli.output.clone().or_else(|| cli.download.then(|| download_path(url)));
+    let (Some(range), Some(path)) = (cli.range, path) else {
+        return Ok(None);
+    };
+    let done = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Ok(None),
+    };
+    match range.resume(done) {
+        Some(rest) => Ok(Some((path, rest))),
+        None => Err(anyhow!("{} already holds the whole range {}", path.display(), range)),
+    }
+}
+
+/// A response whose status --expect-status didn't allow.
+#[derive(Debug)]
+struct UnexpectedStatus(StatusMatcher, StatusCode);
+
+impl std::fmt::Display for UnexpectedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected status {}, got {}", self.0, self.1)
+    }
+}
+
+impl std::error::Error for UnexpectedStatus {}
+
+/// Fails after the response has been printed when it doesn't have the expected status.
+fn check_status(cli: &Cli, status: StatusCode) -> Result<()> {
+    match cli.expect_status {
+        Some(expected) if !expected.matches(status) => Err(UnexpectedStatus(expected, status).into()),
+        _ => Ok(()),
+    }
+}
+
+async fn execute(ctx: &Context<'_>, request: Request) -> Result<Response> {
+    Ok(match &ctx.cli.unix_socket {
+        #[cfg(unix)]
+        Some(path) => unix::execute(path, request).await?,
+        #[cfg(not(unix))]
+        Some(_) => return Err(anyhow!("--unix-socket is only supported on Unix")),
+        None if ctx.cli.debug => {
+            debug::phase(format_args!("{} {}", request.method(), request.url()));
+            let start = Instant::now();
+            let response = ctx.client.execute(request).await.map_err(|e| execute_error(ctx, e))?;
+            debug::phase(format_args!("{} headers after {:.2?}", response.status(), start.elapsed()));
+            response
+        }
+        None => ctx.client.execute(request).await.map_err(|e| execute_error(ctx, e))?,
+    })
+}
+
+/// Explains a failure to speak HTTP/2 with prior knowledge.
+fn execute_error(ctx: &Context<'_>, e: reqwest::Error) -> anyhow::Error {
+    let rejected = ctx.cli.http2 && !e.is_connect() && !e.is_timeout();
+    let err = anyhow::Error::new(e);
+    if rejected {
+        err.context("The server did not accept HTTP/2 with prior knowledge (--http2)")
+    } else {
+        err
+    }
+}
+
+/// Sends `request` and reads the whole body, returning the status and how long it took.
+async fn timed(ctx: &Context<'_>, request: Request) -> Result<(StatusCode, Duration)> {
+    let start = Instant::now();
+    let response = execute(ctx, request).await?;
+    let status = response.status();
+    response.bytes().await?;
+    Ok((status, start.elapsed()))
+}
+
+/// How long the `i`th --repeat request waits for --delay: the delay itself after the previous
+/// one when they run one at a time, or until its slot in a schedule spaced `delay` apart.
+fn request_wait(delay: Duration, i: usize, limit: usize, started: Instant) -> Option<Duration> {
+    match (i, limit) {
+        (0, _) => None,
+        (_, 1) => Some(delay),
+        _ => (started + delay * i as u32).checked_duration_since(Instant::now()),
+    }
+}
+
+/// Spaces out request starts for --rate-limit, however many requests are waiting at once.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> Self {
+        Self { interval, next: Mutex::new(None) }
+    }
+
+    /// Waits for the next free slot, claiming it before sleeping so waiters queue up in turn.
+    async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + self.interval);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+async fn benchmark(ctx: &Context<'_>, request: Request, n: usize) -> Result<()> {
+    let limit = ctx.cli.concurrency.map_or(1, NonZeroUsize::get);
+    let started = Instant::now();
+    let delay = ctx.cli.delay.map(Duration::from_millis);
+    let limiter = ctx.cli.rate_limit.map(RateLimiter::new);
+    let results: Vec<_> = stream::iter(0..n)
+        .map(|i| {
+            let request = &request;
+            let limiter = limiter.as_ref();
+            async move {
+                let request = request
+                    .try_clone()
+                    .ok_or_else(|| anyhow!("A streaming body can't be sent more than once"))?;
+                if let Some(wait) = delay.and_then(|delay| request_wait(delay, i, limit, started)) {
+                    tokio::time::sleep(wait).await;
+                }
+                if let Some(limiter) = limiter {
+                    limiter.acquire().await;
+                }
+                timed(ctx, request).await
+            }
+        })
+        .buffer_unordered(limit)
+        .collect()
+        .await;
+    let total = started.elapsed();
+
+    let mut latencies = Vec::new();
+    let mut statuses = BTreeMap::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok((status, latency)) => {
+                latencies.push(latency);
+                *statuses.entry(status.as_u16()).or_insert(0) += 1;
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+
+    println!(
+        "{} requests in {:.2?} ({:.1} req/s), {} failed",
+        n,
+        total,
+        n as f64 / total.as_secs_f64(),
+        errors.len()
+    );
+    if let Some(stats) = LatencyStats::new(&mut latencies) {
+        println!(
+            "latency  min {:.2?}  mean {:.2?}  p50 {:.2?}  p95 {:.2?}  max {:.2?}",
+            stats.min, stats.mean, stats.p50, stats.p95, stats.max
+        );
+    }
+    let statuses: Vec<_> = statuses.iter().map(|(status, count)| format!("{}: {}", status, count)).collect();
+    if !statuses.is_empty() {
+        println!("status   {}", statuses.join("  "));
+    }
+    match errors.first() {
+        None => Ok(()),
+        Some(err) => Err(anyhow!(
+            "{} of {} requests failed, first: {}",
+            errors.len(),
+            n,
+            describe_error(err).unwrap_or_else(|| format!("{:#}", err))
+        )),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LatencyStats {
+    min: Duration,
+    max: Duration,
+    mean: Duration,
+    p50: Duration,
+    p95: Duration,
+}
+
+impl LatencyStats {
+    fn new(latencies: &mut [Duration]) -> Option<Self> {
+        latencies.sort();
+        // Nearest-rank percentile.
+        let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+        Some(Self {
+            min: *latencies.first()?,
+            max: *latencies.last()?,
+            mean: latencies.iter().sum::<Duration>() / latencies.len() as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+        })
+    }
+}
+
+fn print_request(req: &Request, cli: &Cli) -> Result<()> {
+    let print = cli.print_flags();
+    if cli.output_format.is_json() {
+        return print_request_json(req, cli);
+    }
+    if print.request_headers {
+        match cli.output_format {
+            OutputFormat::Curl => {
+                let target = match req.url().query() {
+                    Some(query) => format!("{}?{}", req.url().path(), query),
+                    None => req.url().path().to_string(),
+                };
+                println!("{}", format!("> {} {} {:?}", req.method(), target, req.version()).blue());
+                if let Some(decoded) = percent_decoded(&target) {
+                    println!("{}", format!("* decoded: {}", decoded).dimmed());
+                }
+            }
+            _ => {
+                println!("{}", format!("{} {} {:?}", req.method(), req.url(), req.version()).blue());
+                if let Some(decoded) = percent_decoded(req.url().as_str()) {
+                    println!("{}", format!("decoded: {}", decoded).dimmed());
+                }
+                println!();
+            }
+        }
+        print_header(req.headers(), cli, "> ");
+    }
+    if print.request_body {
+        if let Some((body, readable)) = request_body_text(req) {
+            let mime = get_content_type(req.headers()).filter(|_| readable);
+            print_body(mime, &body, JsonFormat::default(), None)?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// `s` with percent-escapes decoded for reading, when that changes it and gives valid UTF-8.
+fn percent_decoded(s: &str) -> Option<String> {
+    let decoded = percent_encoding::percent_decode_str(s).decode_utf8().ok()?;
+    (decoded != s).then(|| decoded.into_owned())
+}
+
+fn print_request_json(req: &Request, cli: &Cli) -> Result<()> {
+    let print = cli.print_flags();
+    if !print.request_headers && !print.request_body {
+        return Ok(());
+    }
+    let mut object = Map::new();
+    if print.request_headers {
+        object.insert("method".into(), req.method().as_str().into());
+        object.insert("url".into(), req.url().as_str().into());
+        object.insert("version".into(), format!("{:?}", req.version()).into());
+        object.insert("headers".into(), headers_json(req.headers(), cli.header_display()));
+    }
+    if print.request_body {
+        if let Some((body, readable)) = request_body_text(req) {
+            let mime = get_content_type(req.headers()).filter(|_| readable);
+            let value = body_json(mime.as_ref(), &body, JsonFormat::default()).unwrap_or_else(|_| body.into());
+            object.insert("body".into(), value);
+        }
+    }
+    print_json_object(object, cli, &mut io::stdout().lock())
+}
+
+fn print_status(resp: &Response, format: OutputFormat) {
+    let cached = if resp.status() == StatusCode::NOT_MODIFIED { " (cached)" } else { "" };
+    let status = format!("{:?} {}{}", resp.version(), resp.status(), cached).blue();
+    match format {
+        OutputFormat::Curl => println!("{} {}", "<".blue(), status),
+        _ => println!("{}\n", status),
+    }
+}
+
+/// How printed headers are ordered, and which values are hidden or shortened.
+#[derive(Debug, Clone, Copy, Default)]
+struct HeaderDisplay<'a> {
+    sort: bool,
+    truncate: Option<usize>,
+    hidden: &'a [HeaderName],
+}
+
+impl HeaderDisplay<'_> {
+    fn is_hidden(&self, name: &HeaderName) -> bool {
+        self.hidden.contains(name)
+    }
+
+    fn value(&self, name: &HeaderName, value: &HeaderValue) -> String {
+        if self.is_hidden(name) {
+            "[redacted]".to_string()
+        } else {
+            self.shorten(format_header_value(value))
+        }
+    }
+
+    fn shorten(&self, text: String) -> String {
+        match self.truncate {
+            Some(n) if text.chars().count() > n => format!("{}…", text.chars().take(n).collect::<String>()),
+            _ => text,
+        }
+    }
+}
+
+/// One line per value with the colons lined up; a header with several values, like
+/// Set-Cookie, gets several lines.
+fn format_headers(headers: &HeaderMap, display: HeaderDisplay) -> Vec<String> {
+    let mut lines: Vec<_> = headers.iter().collect();
+    if display.sort {
+        // Stable, so repeated values keep their order.
+        lines.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+    }
+    let width = lines.iter().map(|(name, _)| name.as_str().len()).max().unwrap_or(0);
+    let mut out = Vec::new();
+    for (name, value) in lines {
+        let padded = format!("{:>width$}", name.as_str()).green();
+        if name != header::SET_COOKIE || display.is_hidden(name) {
+            out.push(format!("{}: {}", padded, display.value(name, value)));
+            continue;
+        }
+        let mut cookie = format_cookie(value).into_iter().map(|line| display.shorten(line));
+        out.push(format!("{}: {}", padded, cookie.next().unwrap_or_default()));
+        out.extend(cookie.map(|attribute| format!("{:width$}  {}", "", attribute)));
+    }
+    out
+}
+
+/// The value as text; bytes that aren't valid UTF-8 are shown as replacement characters.
+fn format_header_value(value: &HeaderValue) -> String {
+    match value.to_str() {
+        Ok(value) => value.to_string(),
+        Err(_) => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+    }
+}
+
+/// Splits a Set-Cookie value into the cookie itself followed by one attribute per line.
+fn format_cookie(value: &HeaderValue) -> Vec<String> {
+    let value = String::from_utf8_lossy(value.as_bytes());
+    let mut parts = value.split(';').map(str::trim).filter(|part| !part.is_empty());
+    let cookie = match parts.next().map(|c| c.split_once('=').unwrap_or(("", c))) {
+        Some((name, value)) => format!("{} = {}", name.yellow(), value),
+        None => String::new(),
+    };
+    std::iter::once(cookie)
+        .chain(parts.map(|attribute| match attribute.split_once('=') {
+            Some((k, v)) => format!("{}: {}", k.trim(), v.trim()),
+            None => attribute.to_string(),
+        }))
+        .collect()
+}
+
+/// Prints headers in the chosen layout; `curl` puts `prefix` before each line, like `curl -v`.
+fn print_header(headers: &HeaderMap, cli: &Cli, prefix: &str) {
+    match cli.output_format {
+        OutputFormat::Curl => {
+            for (name, value) in headers {
+                println!("{}{}: {}", prefix, name.to_string().green(), cli.header_display().value(name, value));
+            }
+            println!("{}", prefix.trim_end());
+        }
+        _ => {
+            for line in format_headers(headers, cli.header_display()) {
+                println!("{}", line);
+            }
+            println!()
+        }
+    }
+}
+
+fn is_json(m: Option<&Mime>) -> bool {
+    matches!(m, Some(v) if *v == APPLICATION_JSON)
+}
+
+fn is_event_stream(m: Option<&Mime>) -> bool {
+    matches!(m, Some(v) if v.type_() == mime::TEXT && v.subtype() == mime::EVENT_STREAM)
+}
+
+fn is_ndjson(m: Option<&Mime>) -> bool {
+    matches!(m, Some(v) if v.type_() == mime::APPLICATION && matches!(v.subtype().as_str(), "x-ndjson" | "ndjson" | "jsonl"))
+}
+
+/// How a JSON body is narrowed down and laid out before printing.
+#[derive(Debug, Clone, Copy, Default)]
+struct JsonFormat<'a> {
+    filter: Option<&'a Filter>,
+    pointer: Option<&'a str>,
+    sorted: bool,
+    compact: bool,
+    /// Leave the body as it came unless it's being narrowed down, sorted or compacted.
+    raw: bool,
+}
+
+impl JsonFormat<'_> {
+    fn selects(&self) -> bool {
+        self.filter.is_some() || self.pointer.is_some()
+    }
+}
+
+/// The part of `value` picked by --filter or --select, or all of it.
+fn select_json<'v>(value: &'v Value, format: JsonFormat) -> Result<&'v Value> {
+    Ok(match (format.filter, format.pointer) {
+        (Some(f), _) => f.apply(value)?,
+        (_, Some(p)) => value
+            .pointer(p)
+            .ok_or_else(|| anyhow!("JSON Pointer {} does not match the response", p))?,
+        _ => value,
+    })
+}
+
+fn render_json(body: &str, format: JsonFormat) -> Result<String> {
+    if !format.selects() && !format.sorted {
+        return Ok(if format.raw && !format.compact {
+            body.to_string()
+        } else if format.compact {
+            jsonxf::minimize(body).map_err(|e| anyhow!(e))?
+        } else {
+            jsonxf::pretty_print(body).map_err(|e| anyhow!(e))?
+        });
+    }
+    let value: Value = serde_json::from_str(body)?;
+    // `Value` keeps object keys in a BTreeMap, so a round trip sorts them.
+    let selected = select_json(&value, format)?;
+    Ok(if format.compact {
+        serde_json::to_string(selected)?
+    } else {
+        serde_json::to_string_pretty(selected)?
+    })
+}
+
+/// Which body lines `--grep` keeps.
+#[derive(Debug, Clone, Copy)]
+struct Grep<'a> {
+    pattern: &'a Regex,
+    invert: bool,
+}
+
+impl Grep<'_> {
+    /// Returns the kept lines, with matches highlighted and the rest painted by `paint`.
+    fn lines(&self, text: &str, paint: impl Fn(&str) -> String) -> Vec<String> {
+        text.lines()
+            .filter(|line| self.pattern.is_match(line) != self.invert)
+            .map(|line| {
+                let mut out = String::new();
+                let mut last = 0;
+                for m in self.pattern.find_iter(line).filter(|_| !self.invert) {
+                    out.push_str(&paint(&line[last..m.start()]));
+                    out.push_str(&m.as_str().bright_red().bold().to_string());
+                    last = m.end();
+                }
+                out.push_str(&paint(&line[last..]));
+                out
+            })
+            .collect()
+    }
+}
+
+fn print_body(m: Option<Mime>, body: &str, format: JsonFormat, grep: Option<Grep>) -> Result<()> {
+    let (text, json) = match m {
+        ref v if is_json(v.as_ref()) => match render_json(body, format) {
+            Ok(text) => (text, true),
+            // A body labelled JSON that isn't is still worth seeing.
+            Err(_) if !format.selects() => (body.to_string(), false),
+            Err(err) => return Err(err),
+        },
+        _ if format.selects() => return Err(anyhow!("Cannot apply --filter or --select to a non-JSON response")),
+        _ => (body.to_string(), false),
+    };
+    let paint = |s: &str| if json && !s.is_empty() { s.cyan().to_string() } else { s.to_string() };
+    match grep {
+        Some(grep) => grep.lines(&text, paint).iter().for_each(|line| println!("{}", line)),
+        None => println!("{}", paint(&text)),
+    }
+    Ok(())
+}
+
+/// The Content-Type, or `None` when there's none or it isn't a valid MIME type.
+fn get_content_type(headers: &HeaderMap) -> Option<Mime> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+/// The part of `chunk` that fits under `limit` after `read` bytes, and whether it was cut.
+fn capped(chunk: &[u8], read: u64, limit: Option<u64>) -> (&[u8], bool) {
+    match limit {
+        Some(limit) if read + chunk.len() as u64 > limit => (&chunk[..limit.saturating_sub(read) as usize], true),
+        _ => (chunk, false),
+    }
+}
+
+fn too_large(limit: Option<u64>) -> anyhow::Error {
+    anyhow!("The response body is larger than --max-response-size {} bytes; the rest wasn't read", limit.unwrap_or_default())
+}
+
+/// Reads the whole body, or its first `limit` bytes and `true` when there's more.
+async fn read_body(resp: &mut Response, limit: Option<u64>) -> Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    while let Some(chunk) = next_chunk(resp, body.len() as u64).await? {
+        let (chunk, cut) = capped(&chunk, body.len() as u64, limit);
+        body.extend_from_slice(chunk);
+        if cut {
+            return Ok((body, true));
+        }
+    }
+    Ok((body, false))
+}
+
+/// The next chunk of the body, `read` bytes in. When the connection closes before
+/// `Content-Length` bytes arrived, that's reported as a warning and taken as the end of the
+/// body, so what did arrive is still shown.
+async fn next_chunk(resp: &mut Response, read: u64) -> Result<Option<hyper::body::Bytes>> {
+    match resp.chunk().await {
+        Ok(chunk) => Ok(chunk),
+        Err(err) if is_truncation(&err) => {
+            let expected = resp.content_length().map_or("more".to_string(), |n| n.to_string());
+            eprintln!("{} body truncated: expected {}, got {}", "warning:".yellow(), expected, read);
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Whether a body read failed because the connection ended early.
+fn is_truncation(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if err.downcast_ref::<io::Error>().is_some_and(|err| err.kind() == io::ErrorKind::UnexpectedEof) {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Waits until `read` bytes are no more than `rate` bytes a second since `started`.
+async fn throttle(started: Instant, read: u64, rate: Option<u64>) {
+    if let Some(rate) = rate {
+        let due = started + Duration::from_secs_f64(read as f64 / rate as f64);
+        tokio::time::sleep_until(due.into()).await;
+    }
+}
+
+/// Copies the body to `out` chunk by chunk so it is never held in memory at once, failing
+/// after `limit` bytes and slowing down to `rate` bytes a second. Returns the number of bytes
+/// written.
+async fn stream_body(resp: &mut Response, out: &mut impl Write, progress: &ProgressBar, limit: Option<u64>, rate: Option<u64>) -> Result<u64> {
+    let started = Instant::now();
+    while let Some(chunk) = next_chunk(resp, progress.position()).await? {
+        let (chunk, cut) = capped(&chunk, progress.position(), limit);
+        out.write_all(chunk)?;
+        progress.inc(chunk.len() as u64);
+        throttle(started, progress.position(), rate).await;
+        if cut {
+            out.flush()?;
+            progress.finish_and_clear();
+            return Err(too_large(limit));
+        }
+    }
+    out.flush()?;
+    progress.finish_and_clear();
+    Ok(progress.position())
+}
+
+/// Prints each line of a newline-delimited JSON body as soon as it has fully arrived; lines
+/// that aren't JSON are printed as they are. Returns the number of bytes read.
+async fn stream_ndjson(resp: &mut Response, out: &mut impl Write, format: JsonFormat<'_>, limit: Option<u64>) -> Result<u64> {
+    let mut size = 0;
+    let mut pending = Vec::new();
+    let print_line = |line: &[u8], out: &mut dyn Write| -> Result<()> {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        // jsonxf reformats whatever it's given, so check the line is JSON first.
+        let rendered = serde_json::from_str::<serde::de::IgnoredAny>(line)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| render_json(line, format));
+        match rendered {
+            Ok(json) => writeln!(out, "{}", json.cyan())?,
+            Err(_) => writeln!(out, "{}", line)?,
+        }
+        out.flush()?;
+        Ok(())
+    };
+    while let Some(chunk) = next_chunk(resp, size).await? {
+        let (chunk, cut) = capped(&chunk, size, limit);
+        size += chunk.len() as u64;
+        pending.extend_from_slice(chunk);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            print_line(&line[..end], out)?;
+        }
+        if cut {
+            print_line(&pending, out)?;
+            return Err(too_large(limit));
+        }
+    }
+    print_line(&pending, out)?;
+    Ok(size)
+}
+
+/// Prints a server-sent event stream line by line as it arrives, with the field names colored,
+/// until it ends or `max_events` events have been seen. Returns the number of bytes read.
+async fn stream_events(resp: &mut Response, out: &mut impl Write, max_events: Option<NonZeroUsize>, limit: Option<u64>) -> Result<u64> {
+    let mut size = 0;
+    let mut pending = Vec::new();
+    let mut events = 0;
+    let mut in_event = false;
+    while let Some(chunk) = next_chunk(resp, size).await? {
+        let (chunk, cut) = capped(&chunk, size, limit);
+        size += chunk.len() as u64;
+        pending.extend_from_slice(chunk);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                // A blank line ends the event, if one has started.
+                if in_event {
+                    writeln!(out)?;
+                    events += 1;
+                    in_event = false;
+                }
+                if max_events.is_some_and(|max| events >= max.get()) {
+                    out.flush()?;
+                    return Ok(size);
+                }
+                continue;
+            }
+            // Comments, which servers send to keep the connection open, aren't events.
+            in_event |= !line.starts_with(':');
+            writeln!(out, "{}", format_event_line(line))?;
+        }
+        out.flush()?;
+        if cut {
+            return Err(too_large(limit));
+        }
+    }
+    Ok(size)
+}
+
+fn format_event_line(line: &str) -> String {
+    if line.starts_with(':') {
+        return line.dimmed().to_string();
+    }
+    let (field, value) = line.split_once(':').unwrap_or((line, ""));
+    let field = match field {
+        "event" => field.yellow().bold(),
+        "data" => field.green(),
+        _ => field.dimmed(),
+    };
+    format!("{}:{}", field, value)
+}
+
+/// A bar on stderr when the size is known, otherwise a spinner with the byte count and rate.
+fn download_progress(resp: &Response) -> ProgressBar {
+    match resp.content_length() {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template("{bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})").unwrap(),
+        ),
+        None => ProgressBar::new_spinner()
+            .with_style(ProgressStyle::with_template("{spinner} {bytes} ({bytes_per_sec})").unwrap()),
+    }
+}
+
+fn download_path(url: &Url) -> PathBuf {
+    let name = url.path_segments().and_then(|mut s| s.next_back());
+    PathBuf::from(name.filter(|name| !name.is_empty()).unwrap_or("index"))
+}
+
+fn format_meta(size: u64, mime: Option<&Mime>, version: Version, encoding: Option<&str>) -> String {
+    let mime = mime.map(|m| m.essence_str().to_string());
+    let meta = format!("{} bytes, {}, {:?}", size, mime.as_deref().unwrap_or("no content type"), version);
+    match encoding {
+        Some(encoding) => format!("{}, {}-encoded", meta, encoding),
+        None => meta,
+    }
+}
+
+/// Prints the response, returning it with the body if it had to be read whole to print it; a
+/// body streamed to stdout or a file isn't kept. `resume` names a partly downloaded file that a
+/// 206 response is appended to.
+///
+/// Printing happens here rather than in `run` because most bodies are printed or saved as they
+/// arrive, so there's no whole body to hand back for the caller to print.
+async fn print_resp(mut resp: Response, cli: &Cli, resume: Option<&Path>) -> Result<ResponseSummary> {
+    let print = cli.print_flags();
+    let mut summary = ResponseSummary {
+        status: resp.status(),
+        version: resp.version(),
+        headers: resp.headers().clone(),
+        body: Vec::new(),
+        elapsed: Duration::ZERO,
+    };
+    if cli.range.is_some() && resp.status() == StatusCode::OK {
+        let restart = if resume.is_some() { "; starting the download over" } else { "" };
+        eprintln!("{} the server ignored the range and sent the whole body{}", "warning:".yellow(), restart);
+    }
+    if let Some(path) = &cli.output_headers {
+        write_headers_file(path, &resp)?;
+    }
+    let path = match (resume, &cli.output) {
+        (Some(path), _) => Some(path.to_path_buf()),
+        (None, Some(path)) => Some(path.clone()),
+        (None, None) if cli.download => Some(download_path(resp.url())),
+        (None, None) => None,
+    };
+    if cli.output_format.is_json() && !cli.quiet {
+        summary.body = print_resp_json(resp, cli, path, resume.is_some(), &mut io::stdout().lock()).await?;
+        return Ok(summary);
+    }
+    if print.response_headers {
+        print_status(&resp, cli.output_format);
+        print_header(resp.headers(), cli, "< ");
+    }
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        // There's no body, and an --output file must keep the copy it already has.
+        if !print.response_headers && !cli.quiet {
+            println!("{}", "304 Not Modified (cached)".blue());
+        }
+        return Ok(summary);
+    }
+    if !print.response_body && !cli.quiet {
+        if cli.schema.is_some() {
+            let (body, cut) = read_body(&mut resp, cli.max_response_size).await?;
+            if cut {
+                return Err(too_large(cli.max_response_size));
+            }
+            summary.body = client::decode_body(resp.headers(), body)?;
+        }
+        return Ok(summary);
+    }
+    let mime = get_content_type(resp.headers());
+    let version = resp.version();
+    let url = resp.url().clone();
+    let encoding = resp
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(format_header_value)
+        .filter(|encoding| !encoding.eq_ignore_ascii_case("identity"));
+    let limit = cli.max_response_size;
+    let size = if let Some(path) = path {
+        save_body(&mut resp, cli, &path, resume.is_some()).await?
+    } else if cli.quiet {
+        return Ok(summary);
+    } else if let Some(encoding) = &encoding {
+        let (raw, cut) = read_body(&mut resp, limit).await?;
+        if cut {
+            println!("{}", format!("[first {} bytes of {}-encoded body not shown]", raw.len(), encoding).yellow());
+            return Err(too_large(limit));
+        }
+        let (size, body) = print_encoded_body(&raw, encoding, mime.clone(), cli)?;
+        summary.body = body.unwrap_or_default();
+        size
+    } else if is_event_stream(mime.as_ref()) && cli.grep.is_none() && cli.schema.is_none() {
+        stream_events(&mut resp, &mut io::stdout().lock(), cli.max_events, limit).await?
+    } else if is_ndjson(mime.as_ref()) && cli.grep.is_none() && cli.schema.is_none() {
+        stream_ndjson(&mut resp, &mut io::stdout().lock(), cli.json_format(), limit).await?
+    } else if is_json(mime.as_ref()) || cli.json_format().selects() || cli.grep.is_some() || cli.schema.is_some() {
+        // Pretty-printing, filtering, grepping and validating need the whole document.
+        let (body, cut) = read_body(&mut resp, limit).await?;
+        let text = String::from_utf8_lossy(&body);
+        if cut {
+            println!("{}", text);
+            return Err(too_large(limit));
+        }
+        print_body(mime.clone(), &text, cli.json_format(), cli.grep())?;
+        summary.body = body;
+        summary.body.len() as u64
+    } else {
+        let size = stream_body(&mut resp, &mut io::stdout().lock(), &ProgressBar::hidden(), limit, cli.limit_rate).await;
+        println!();
+        size?
+    };
+    if cli.meta || cli.verbose {
+        println!("\n{}", format_meta(size, mime.as_ref(), version, encoding.as_deref()).dimmed());
+        println!("{}", format!("from {}", url).dimmed());
+    }
+    Ok(summary)
+}
+
+/// Prints a body decoded from `encoding`, or a note when it can't be, returning the decoded
+/// body and its size; the size is the encoded one when decoding failed.
+fn print_encoded_body(raw: &[u8], encoding: &str, mime: Option<Mime>, cli: &Cli) -> Result<(u64, Option<Vec<u8>>)> {
+    match encoding::decode(encoding, raw) {
+        Ok(body) => {
+            print_body(mime, &String::from_utf8_lossy(&body), cli.json_format(), cli.grep())?;
+            Ok((body.len() as u64, Some(body)))
+        }
+        Err(err) => {
+            println!("{}", format!("[{} bytes of {}-encoded body not shown: {}]", raw.len(), encoding, err).yellow());
+            Ok((raw.len() as u64, None))
+        }
+    }
+}
+
+/// Writes the status line and headers as `Name: value` lines, ending with a blank line.
+fn write_headers_file(path: &Path, resp: &Response) -> Result<()> {
+    let mut text = format!("{:?} {}\n", resp.version(), resp.status());
+    for (name, value) in resp.headers() {
+        text.push_str(&format!("{}: {}\n", name, format_header_value(value)));
+    }
+    text.push('\n');
+    std::fs::write(path, text).map_err(|e| anyhow!("Failed to write headers to {}: {}", path.display(), e))
+}
+
+/// Writes the body to `path`, appending to it when resuming and the server sent only the
+/// missing part.
+async fn save_body(resp: &mut Response, cli: &Cli, path: &Path, resuming: bool) -> Result<u64> {
+    let (mut file, action) = if resuming && resp.status() == StatusCode::PARTIAL_CONTENT {
+        (OpenOptions::new().append(true).open(path)?, "Resuming download")
+    } else if cli.append {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if cli.append_stamp {
+            writeln!(file, "=== {} {} ===", httpdate::fmt_http_date(SystemTime::now()), resp.url())?;
+        }
+        (file, "Appending")
+    } else {
+        (File::create(path)?, "Downloading")
+    };
+    let progress = if cli.quiet {
+        ProgressBar::hidden()
+    } else {
+        eprintln!("{} to {}", action, path.display());
+        download_progress(resp)
+    };
+    // Deleting an appended-to file on Ctrl-C would lose what it held before.
+    let _partial = (!cli.append).then(|| PartialFile::register(path, cli.range.is_some()));
+    stream_body(resp, &mut file, &progress, cli.max_response_size, cli.limit_rate).await
+}
+
+/// Files with a body still being written, and whether each should be kept for resuming.
+static PARTIAL_FILES: Mutex<Vec<(PathBuf, bool)>> = Mutex::new(Vec::new());
+
+/// Marks a file as partly written until dropped, so Ctrl-C can clean it up.
+struct PartialFile(PathBuf);
+
+impl PartialFile {
+    fn register(path: &Path, keep: bool) -> Self {
+        PARTIAL_FILES.lock().unwrap().push((path.to_path_buf(), keep));
+        Self(path.to_path_buf())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        PARTIAL_FILES.lock().unwrap().retain(|(path, _)| *path != self.0);
+    }
+}
+
+/// Deletes the files an interrupted run left half written, except those --range can resume.
+fn clean_up_partial_files() {
+    let files: Vec<_> = PARTIAL_FILES.lock().unwrap().drain(..).collect();
+    clean_up(files);
+}
+
+fn clean_up(files: Vec<(PathBuf, bool)>) {
+    for (path, keep) in files {
+        if keep {
+            eprintln!("Kept partial download {}; run again with --range to resume", path.display());
+        } else if std::fs::remove_file(&path).is_ok() {
+            eprintln!("Removed partial download {}", path.display());
+        }
+    }
+}
+
+/// Prints the response as one JSON object holding the parts chosen by --print. A body saved
+/// to a file is replaced by the file's path. Returns the body when it was printed.
+async fn print_resp_json(mut resp: Response, cli: &Cli, path: Option<PathBuf>, resuming: bool, out: &mut impl Write) -> Result<Vec<u8>> {
+    let print = cli.print_flags();
+    let mut object = Map::new();
+    let mut printed = Vec::new();
+    if print.response_headers {
+        object.insert("status".into(), resp.status().as_u16().into());
+        object.insert("reason".into(), resp.status().canonical_reason().unwrap_or_default().into());
+        object.insert("version".into(), format!("{:?}", resp.version()).into());
+        object.insert("headers".into(), headers_json(resp.headers(), cli.header_display()));
+    }
+    if print.response_body {
+        match path {
+            Some(path) => {
+                save_body(&mut resp, cli, &path, resuming).await?;
+                object.insert("saved_to".into(), path.display().to_string().into());
+            }
+            None => {
+                let mime = get_content_type(resp.headers());
+                let encoding = resp.headers().get(header::CONTENT_ENCODING).map(format_header_value);
+                let (raw, cut) = read_body(&mut resp, cli.max_response_size).await?;
+                if cut {
+                    return Err(too_large(cli.max_response_size));
+                }
+                let body = match encoding {
+                    Some(encoding) => encoding::decode(&encoding, &raw)?,
+                    None => raw,
+                };
+                object.insert("body".into(), body_json(mime.as_ref(), &String::from_utf8_lossy(&body), cli.json_format())?);
+                printed = body;
+            }
+        }
+    }
+    print_json_object(object, cli, out)?;
+    Ok(printed)
+}
+
+fn print_json_object(object: Map<String, Value>, cli: &Cli, out: &mut impl Write) -> Result<()> {
+    let text = match cli.output_format {
+        OutputFormat::Ndjson => serde_json::to_string(&object)?,
+        _ => serde_json::to_string_pretty(&object)?,
+    };
+    writeln!(out, "{}", text)?;
+    Ok(())
+}
+
+/// Header names mapped to their value, or to an array when a header has several.
+fn headers_json(headers: &HeaderMap, display: HeaderDisplay) -> Value {
+    let mut object = Map::new();
+    for name in headers.keys() {
+        let mut values: Vec<Value> = headers.get_all(name).iter().map(|v| display.value(name, v).into()).collect();
+        let value = if values.len() == 1 { values.remove(0) } else { Value::Array(values) };
+        object.insert(name.to_string(), value);
+    }
+    Value::Object(object)
+}
+
+/// A JSON body as JSON, narrowed down by --filter or --select, and anything else as a string.
+fn body_json(mime: Option<&Mime>, body: &str, format: JsonFormat) -> Result<Value> {
+    if is_json(mime) {
+        let value: Value = serde_json::from_str(body)?;
+        Ok(select_json(&value, format)?.clone())
+    } else if format.selects() {
+        Err(anyhow!("Cannot apply --filter or --select to a non-JSON response"))
+    } else {
+        Ok(body.into())
+    }
+}
+
+/// The timeouts in effect and where each was set, for naming them when they fire.
+#[derive(Debug, Default, Clone, Copy)]
+struct Timeouts {
+    connect: Option<(Duration, &'static str)>,
+    total: Option<(Duration, &'static str)>,
+}
+
+static TIMEOUTS: OnceLock<Timeouts> = OnceLock::new();
+
+/// How a timeout message names the limit, like `within the 2s --timeout`.
+fn limit_text(limit: Option<(Duration, &str)>, fallback: &str) -> String {
+    match limit {
+        Some((duration, source)) => format!("within the {:?} {}", duration, source),
+        None => fallback.to_string(),
+    }
+}
+
+/// Turns common transport failures into a one-line message.
+fn describe_error(err: &anyhow::Error) -> Option<String> {
+    let e = err.downcast_ref::<reqwest::Error>()?;
+    let host = e.url().and_then(|url| url.host_str()).unwrap_or("host");
+    let addr = match e.url().and_then(|url| url.port_or_known_default()) {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    let timeouts = TIMEOUTS.get().copied().unwrap_or_default();
+    if e.is_connect() && e.is_timeout() {
+        Some(format!("could not connect to {} {}", addr, limit_text(timeouts.connect, "in time")))
+    } else if e.is_timeout() {
+        Some(format!("{} did not respond {}", addr, limit_text(timeouts.total, "in time")))
+    } else if e.is_connect() && is_dns_error(err) {
+        Some(format!("could not resolve {}", host))
+    } else if e.is_connect() {
+        Some(format!("could not connect to {}", addr))
+    } else {
+        None
+    }
+}
+
+fn is_dns_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.to_string().starts_with("dns error"))
+}
+
+/// A coarse category of `err` for --error-json.
+fn error_kind(err: &anyhow::Error) -> &'static str {
+    if err.is::<UnexpectedStatus>() {
+        return "http_status";
+    }
+    if err.is::<serde_json::Error>() || err.is::<mime::FromStrError>() {
+        return "parse";
+    }
+    let Some(e) = err.downcast_ref::<reqwest::Error>() else {
+        return "other";
+    };
+    let tls = err.chain().any(|cause| {
+        let cause = cause.to_string().to_ascii_lowercase();
+        cause.contains("certificate") || cause.contains("tls") || cause.contains("ssl")
+    });
+    if e.is_timeout() {
+        "timeout"
+    } else if tls {
+        "tls"
+    } else if e.is_decode() || e.is_builder() {
+        "parse"
+    } else if e.is_connect() || e.is_request() || e.is_body() {
+        "network"
+    } else {
+        "other"
+    }
+}
+
+/// Parses a headers file, naming the line of the first malformed header.
+fn parse_headers_file(content: &str) -> Result<Vec<HeaderPair>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(n, line)| line.parse().map_err(|e| anyhow!("line {}: {}", n, e)))
+        .collect()
+}
+
+fn header_overrides(cli: &Cli) -> Result<HeaderMap> {
+    let from_file = match &cli.headers_from {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse_headers_file(&content))
+            .map_err(|e| anyhow!("Failed to read headers file {}: {}", path.display(), e))?,
+        None => Vec::new(),
+    };
+    // A name repeated within the file or within the flags is sent once per value, but a name
+    // given as a flag replaces the file's values.
+    let mut headers = HeaderMap::new();
+    for pairs in [&from_file, &cli.headers] {
+        let mut source = HeaderMap::new();
+        for pair in pairs {
+            let value = match cli.expand_mode() {
+                Some(mode) => expand_env(&pair.value, mode)?,
+                None => pair.value.clone(),
+            };
+            let value = HeaderValue::from_str(&value).map_err(|_| anyhow!("Invalid value for header {}", pair.name))?;
+            source.append(pair.name.clone(), value);
+        }
+        for name in source.keys() {
+            headers.remove(name);
+        }
+        for (name, value) in &source {
+            headers.append(name, value.clone());
+        }
+    }
+    Ok(headers)
+}
+
+fn accept_header(cli: &Cli) -> Option<HeaderValue> {
+    if cli.json {
+        Some(HeaderValue::from_static("application/json, */*;q=0.5"))
+    } else if cli.xml {
+        Some(HeaderValue::from_static("application/xml, text/xml, */*;q=0.5"))
+    } else {
+        cli.accept.clone()
+    }
+}
+
+/// Most redirects followed in a row, as reqwest does by default.
+const MAX_REDIRECTS: usize = 10;
+
+/// Follows up to [`MAX_REDIRECTS`] redirects, printing each hop to stderr when `verbose`.
+fn redirect_policy(verbose: bool) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if verbose {
+            let from = attempt.previous().last().map(Url::as_str).unwrap_or_default();
+            eprintln!("{}", format!("{} {} -> {}", attempt.status(), from, attempt.url()).dimmed());
+        }
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+async fn run(cli: &Cli) -> Result<()> {
+    if cli.output_format.is_json() && cli.grep.is_some() {
+        return Err(anyhow!("--grep can't be used with --output-format json or ndjson"));
+    }
+    // These must work even when the config is broken.
+    if let Command::Config(ConfigCommand::Path) = cli.command {
+        return config_path(cli);
+    }
+    let config = config::load(cli.config.as_deref())?;
+    let color = match (cli.pretty, cli.color.or(config.color).unwrap_or(ColorChoice::Auto)) {
+        (Some(Pretty::All | Pretty::Colors), _) => true,
+        (Some(Pretty::Format | Pretty::None), _) => false,
+        (None, ColorChoice::Auto) => io::stdout().is_terminal(),
+        (None, ColorChoice::Always) => true,
+        (None, ColorChoice::Never) => false,
+    };
+    colored::control::set_override(color);
+
+    let mut headers = HeaderMap::new();
+    if cli.signature {
+        headers.insert("X-POWERED-BY", "Rust".parse()?);
+    }
+    headers.insert(header::USER_AGENT, "Rust Httpie".parse()?);
+    for (name, value) in &config.headers {
+        let name: HeaderName = name.parse().map_err(|_| anyhow!("Invalid header name {} in config", name))?;
+        headers.insert(name, value.parse()?);
+    }
+    if let Some(accept) = accept_header(cli) {
+        headers.insert(header::ACCEPT, accept);
+    }
+    if cli.no_keepalive {
+        headers.insert(header::CONNECTION, HeaderValue::from_static("close"));
+    }
+
+    let follow = if cli.follow || cli.no_follow { cli.follow } else { config.follow_redirects.unwrap_or(true) };
+    let timeouts = Timeouts {
+        connect: cli.connect_timeout.map(|timeout| (timeout, "--connect-timeout")),
+        total: match (cli.timeout, config.timeout) {
+            (Some(timeout), _) => Some((timeout, "--timeout")),
+            (None, Some(secs)) => {
+                let timeout = Duration::try_from_secs_f64(secs).map_err(|e| anyhow!("Invalid timeout {} in config: {}", secs, e))?;
+                Some((timeout, "timeout in the config"))
+            }
+            (None, None) => None,
+        },
+    };
+    let _ = TIMEOUTS.set(timeouts);
+    let client_options = client::ClientOptions {
+        headers: headers.clone(),
+        follow_redirects: follow,
+        timeout: timeouts.total.map(|(duration, _)| duration),
+        connect_timeout: timeouts.connect.map(|(duration, _)| duration),
+        http1_only: cli.http1,
+        http2_prior_knowledge: cli.http2,
+    };
+    let mut builder = client::builder(&client_options);
+    if follow && cli.verbose {
+        builder = builder.redirect(redirect_policy(true));
+    }
+    if cli.debug {
+        debug::init();
+        builder = builder.dns_resolver(debug::TimedResolver::new());
+    }
+    for resolve in &cli.resolve {
+        builder = builder.resolve(&resolve.host, resolve.addr);
+    }
+    match cli.pool_max_idle {
+        _ if cli.no_keepalive => builder = builder.pool_max_idle_per_host(0),
+        Some(n) => builder = builder.pool_max_idle_per_host(n),
+        None => {}
+    }
+    let client = builder.build()?;
+
+    let auth = match &cli.auth {
+        Some(auth) => Some(auth.clone()),
+        None => config.auth.as_deref().map(parse_auth).transpose()?,
+    };
+    let auth = auth.as_ref().map(Auth::resolve).transpose()?;
+    let overrides = header_overrides(cli)?;
+    let netrc_path = match &cli.netrc_file {
+        Some(path) => Some(path.clone()),
+        None if cli.netrc => Some(netrc::default_path().ok_or_else(|| anyhow!("Can't find ~/.netrc without HOME"))?),
+        None => None,
+    };
+    let netrc = netrc_path.as_deref().map(netrc::load).transpose()?;
+    if let (Some(url), Some(id), Some(secret)) = (&cli.oauth2_token_url, &cli.oauth2_client_id, &cli.oauth2_client_secret) {
+        let token = oauth2_token(&client, cli, url, id, secret).await?;
+        // Like the other defaults, it's filled in unless the request has its own.
+        headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse()?);
+    }
+    let host_headers = config
+        .host_headers
+        .iter()
+        .map(|(pattern, headers)| {
+            let mut map = HeaderMap::new();
+            for (name, value) in headers {
+                let name: HeaderName = name.parse().map_err(|_| anyhow!("Invalid header name {} in config", name))?;
+                map.insert(name, value.parse()?);
+            }
+            Ok((pattern.clone(), map))
+        })
+        .collect::<Result<_>>()?;
+    let cache_dir = || cache::default_dir().ok_or_else(|| anyhow!("Can't find a cache directory without HOME"));
+    if cli.cache_clear {
+        cache::clear(&cache_dir()?)?;
+    }
+    let caching = if cli.cache || cli.no_cache { cli.cache } else { config.cache.unwrap_or(false) };
+    let cache = if caching { Some(cache_dir()?) } else { None };
+    let schema = cli.schema.as_deref().map(load_schema).transpose()?;
+    let ctx = Context { client, headers, overrides, auth, netrc, cache, host_headers, cli };
+
+    let summary = match cli.command {
+        Command::Get(ref args) => get(&ctx, args).await?,
+        Command::Options(ref args) => options(&ctx, args).await?,
+        Command::Post(ref args) => post(&ctx, Method::POST, args).await?,
+        Command::Put(ref args) => post(&ctx, Method::PUT, args).await?,
+        Command::Patch(ref args) => post(&ctx, Method::PATCH, args).await?,
+        Command::Graphql(ref args) => graphql(&ctx, args).await?,
+        Command::ImportCurl(ref args) => import_curl(&ctx, args).await?,
+        Command::Diff(ref args) => return diff(&ctx, args).await,
+        Command::Ws(ref args) => return ws(&ctx, args).await,
+        Command::Config(_) => unreachable!("config commands run before the config is loaded"),
+    };
+    match summary {
+        Some(summary) => {
+            if let Some(schema) = &schema {
+                check_schema(schema, &summary)?;
+            }
+            check_status(cli, summary.status)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Compiles the schema up front, so a broken one fails before anything is sent.
+fn load_schema(path: &Path) -> Result<jsonschema::Validator> {
+    let content = std::fs::read(path).map_err(|e| anyhow!("Failed to read schema {}: {}", path.display(), e))?;
+    let schema: Value = serde_json::from_slice(&content).map_err(|e| anyhow!("Failed to parse schema {}: {}", path.display(), e))?;
+    jsonschema::validator_for(&schema).map_err(|e| anyhow!("Invalid schema {}: {}", path.display(), e))
+}
+
+/// Prints each place the body breaks the schema to stderr, failing if there are any.
+fn check_schema(schema: &jsonschema::Validator, summary: &ResponseSummary) -> Result<()> {
+    let body = summary.json()?;
+    let errors: Vec<_> = schema.iter_errors(&body).map(|e| format_schema_error(&e)).collect();
+    for error in &errors {
+        eprintln!("{} {}", "schema:".red(), error);
+    }
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(anyhow!("The response doesn't match the schema")),
+        n => Err(anyhow!("The response doesn't match the schema ({} errors)", n)),
+    }
+}
+
+/// The JSON pointer to the offending value, `/` for the whole body, and what's wrong with it.
+fn format_schema_error(error: &jsonschema::ValidationError) -> String {
+    let path = error.instance_path().to_string();
+    format!("{}: {}", if path.is_empty() { "/" } else { &path }, error)
+}
+
+fn config_path(cli: &Cli) -> Result<()> {
+    let (path, explicit) = config::location(cli.config.as_deref()).ok_or_else(|| anyhow!("Can't find a config directory without HOME"))?;
+    println!("{}", path.display());
+    if !path.exists() {
+        let note = if explicit { "doesn't exist" } else { "doesn't exist; the built-in defaults are used" };
+        eprintln!("{} {}", "note:".yellow(), note);
+    }
+    Ok(())
+}
+
+/// The exit status when --max-time runs out, as with curl.
+const EXIT_TIMEOUT: i32 = 28;
+/// The exit statuses for failing to reach the server, also as with curl.
+const EXIT_DNS: i32 = 6;
+const EXIT_CONNECT: i32 = 7;
+const EXIT_TLS: i32 = 35;
+
+/// The exit status for `err`, telling network failures apart from everything else.
+fn exit_code(err: &anyhow::Error) -> i32 {
+    let connect = err.downcast_ref::<reqwest::Error>().is_some_and(reqwest::Error::is_connect);
+    match error_kind(err) {
+        "timeout" => EXIT_TIMEOUT,
+        "tls" => EXIT_TLS,
+        "network" if connect && is_dns_error(err) => EXIT_DNS,
+        "network" if connect => EXIT_CONNECT,
+        _ => 1,
+    }
+}
+
+/// Completes after `max_time`, or never without one.
+async fn deadline(max_time: Option<Duration>) {
+    match max_time {
+        Some(max_time) => tokio::time::sleep(max_time).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Runs the command line, exiting the process with a failure status on error.
+pub async fn cli_main(cli: Cli) {
+    // Dropping the run future cancels the request in flight.
+    let result = tokio::select! {
+        result = run(&cli) => result,
+        _ = tokio::signal::ctrl_c() => {
+            clean_up_partial_files();
+            eprintln!("{}", "aborted".red());
+            std::process::exit(130);
+        }
+        _ = deadline(cli.max_time) => {
+            // Whatever part of the body was streamed out stays printed.
+            let _ = io::stdout().flush();
+            clean_up_partial_files();
+            let message = format!("gave up after --max-time {:?}", cli.max_time.unwrap_or_default());
+            match cli.error_json {
+                true => eprintln!("\n{}", serde_json::json!({"error": message, "kind": "timeout"})),
+                false => eprintln!("\n{} {}", "error:".red(), message),
+            }
+            std::process::exit(EXIT_TIMEOUT);
+        }
+    };
+    if let Err(err) = result {
+        if cli.error_json {
+            let message = describe_error(&err).unwrap_or_else(|| format!("{:#}", err));
+            eprintln!("{}", serde_json::json!({"error": message, "kind": error_kind(&err)}));
+            std::process::exit(exit_code(&err));
+        }
+        match describe_error(&err) {
+            _ if cli.debug => eprintln!("{} {:?}", "error:".red(), err),
+            Some(message) => eprintln!("{} {} (use --debug for details)", "error:".red(), message),
+            None => eprintln!("{} {:#}", "error:".red(), err),
+        }
+        std::process::exit(exit_code(&err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves one connection with a canned raw response and returns its URL.
+    async fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}/", addr)
+    }
+
+    #[tokio::test]
+    async fn oauth2_token_is_kept_in_session() {
+        let path = std::env::temp_dir().join(format!("httpie-oauth2-session-test-{}.json", std::process::id()));
+        const TOKEN: &str = "HTTP/1.1 200 OK\r\nContent-Length: 40\r\n\r\n{\"access_token\":\"t0k\",\"expires_in\":3600}";
+        let url = serve(TOKEN).await;
+        let args = |session: &str| {
+            let path = path.to_str().unwrap().to_string();
+            Cli::try_parse_from(["httpie", session, &path, "--oauth2-token-url", &url, "--oauth2-client-id", "app", "--oauth2-client-secret", "s", "get", "http://a.b"]).unwrap()
+        };
+        // The second time, the server is gone and the token comes from the session.
+        for _ in 0..2 {
+            assert_eq!(oauth2_token(&Client::new(), &args("--session"), &url, "app", "s").await.unwrap(), "t0k");
+        }
+        let kept = session::load(&path).unwrap().oauth2_token.unwrap();
+        assert!(kept.is_valid_for(&url, "app", None, SystemTime::now()));
+        assert!(oauth2_token(&Client::new(), &args("--session"), &url, "other", "s").await.is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let url = serve(TOKEN).await;
+        assert_eq!(oauth2_token(&Client::new(), &args("--session-read-only"), &url, "app", "s").await.unwrap(), "t0k");
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn execute_cached_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-execute-cached-test-{}", std::process::id()));
+        let cli = Cli::try_parse_from(["httpie", "get", "http://a.b"]).unwrap();
+        let ctx = Context { cache: Some(dir.clone()), ..test_context(&cli) };
+
+        // The second request is answered from the cache, as the server is gone by then.
+        let url = serve("HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok").await;
+        for _ in 0..2 {
+            let response = execute_cached(&ctx, ctx.client.get(&url).build().unwrap()).await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "ok");
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for response in ["HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nConnection: close\r\nContent-Length: 2\r\n\r\nv1", "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let wire = String::from_utf8_lossy(&buf[..n]).into_owned();
+                assert_eq!(wire.contains("if-none-match: \"v1\"\r\n"), response.contains("304"));
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        for _ in 0..2 {
+            let response = execute_cached(&ctx, ctx.client.get(&url).build().unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text().await.unwrap(), "v1");
+        }
+
+        // Nothing is stored for, or answered to, a request with credentials.
+        let url = serve("HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok").await;
+        let request = ctx.client.get(&url).basic_auth("alice", Some("secret")).build().unwrap();
+        assert_eq!(execute_cached(&ctx, request).await.unwrap().text().await.unwrap(), "ok");
+        assert!(cache::load(&dir, &url).is_none());
+
+        // A body that's only partly read isn't stored.
+        let url = serve("HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nConnection: close\r\nContent-Length: 4\r\n\r\nok").await;
+        let response = execute_cached(&ctx, ctx.client.get(&url).build().unwrap()).await.unwrap();
+        assert!(response.text().await.is_err());
+        assert!(cache::load(&dir, &url).is_none());
+        cache::clear(&dir).unwrap();
+        std::fs::remove_dir(&dir).unwrap();
+        assert!(Cli::try_parse_from(["httpie", "--cache", "--no-cache", "get", "http://a.b"]).is_err());
+    }
+
+    #[test]
+    fn parse_url_works() {
+        assert!(parse_url("http://").is_err());
+        assert!(parse_url("abc").is_ok());
+        assert!(parse_url("http://abc.xyz").is_ok());
+        assert!(parse_url("https://httpbin.org/post").is_ok());
+        assert!(parse_url("wss://echo.example.com/socket").is_ok());
+    }
+
+    #[test]
+    fn expand_path_vars_works() {
+        let vars = [("id".to_string(), "42".to_string()), ("name".to_string(), "a b/c".to_string())];
+        assert_eq!(expand_path_vars("https://a.b/users/{id}/files/{name}", &vars).unwrap(), "https://a.b/users/42/files/a%20b%2Fc");
+        assert_eq!(expand_path_vars("https://a.b/{ x }/{", &vars).unwrap(), "https://a.b/{ x }/{");
+        let err = expand_path_vars("https://a.b/orgs/{org}", &vars).unwrap_err();
+        assert_eq!(err.to_string(), "URL https://a.b/orgs/{org} needs {org}; give it with --path-var org=VALUE");
+        assert!(parse_path_var("1d=x").is_err());
+
+        let cli = Cli::try_parse_from(["httpie", "--path-var", "id=7", "get", "http://a.b/users/{id}"]).unwrap();
+        assert_eq!(test_context(&cli).url("http://a.b/users/{id}").unwrap().as_str(), "http://a.b/users/7");
+    }
+
+    #[test]
+    fn context_url_works() {
+        let cli = Cli::try_parse_from(["httpie", "get", "/v1/status"]).unwrap();
+        assert!(test_context(&cli).url("/v1/status").is_err());
+        let cli = Cli::try_parse_from(["httpie", "--unix-socket", "/tmp/app.sock", "get", "/v1/status"]).unwrap();
+        assert_eq!(test_context(&cli).url("/v1/status").unwrap().as_str(), "http://localhost/v1/status");
+        assert_eq!(test_context(&cli).url("http://a.b/c").unwrap().as_str(), "http://a.b/c");
+    }
+
+    #[test]
+    fn default_scheme_works() {
+        let cli = Cli::try_parse_from(["httpie", "get", "localhost:8080/x"]).unwrap();
+        assert_eq!(test_context(&cli).url("localhost:8080/x").unwrap().as_str(), "https://localhost:8080/x");
+        let cli = Cli::try_parse_from(["httpie", "--default-scheme", "http", "get", "a.b"]).unwrap();
+        assert_eq!(test_context(&cli).url("a.b/c").unwrap().as_str(), "http://a.b/c");
+        assert_eq!(test_context(&cli).url("https://a.b/c").unwrap().as_str(), "https://a.b/c");
+    }
+
+    #[test]
+    fn ipv6_urls_work() {
+        assert!(parse_url("http://[2001:db8::1]/").is_ok());
+        assert!(parse_url("[::1]:9000").is_ok());
+        assert!(parse_url("http://[::1/").is_err());
+        let cli = Cli::try_parse_from(["httpie", "--default-scheme", "http", "get", "[::1]:9000"]).unwrap();
+        let ctx = test_context(&cli);
+        assert_eq!(ctx.url("[::1]:9000").unwrap().as_str(), "http://[::1]:9000/");
+        let url = ctx.url("http://[2001:db8::1]/health?a=1").unwrap();
+        assert_eq!(url.host_str(), Some("[2001:db8::1]"));
+        let request = build_request(&ctx, ctx.client.get(url)).unwrap();
+        assert_eq!(request.url().as_str(), "http://[2001:db8::1]/health?a=1");
+    }
+
+    #[test]
+    fn percent_decoded_works() {
+        assert_eq!(percent_decoded("http://a.b/caf%C3%A9?q=a%20b").unwrap(), "http://a.b/café?q=a b");
+        assert_eq!(percent_decoded("http://a.b/plain"), None);
+        assert_eq!(percent_decoded("http://a.b/%FF"), None);
+    }
+
+    #[test]
+    fn parse_kv_pair_works() {
+        assert!(parse_kv_pair("a").is_err());
+        assert_eq!(
+            parse_kv_pair("a=1").unwrap(),
+            KVPair {
+                k: "a".into(),
+                v: "1".into(),
+            }
+        );
+
+        assert_eq!(
+            parse_kv_pair("b=").unwrap(),
+            KVPair {
+                k: "b".into(),
+                v: "".into(),
+            }
+        )
+    }
+
+    #[test]
+    fn parse_kv_pair_json_works() {
+        assert!(parse_kv_pair("a:=nope").is_err());
+        assert_eq!(parse_kv_pair("a:=30").unwrap().v, json!(30));
+        assert_eq!(parse_kv_pair("a:=[1, true]").unwrap().v, json!([1, true]));
+    }
+
+    #[test]
+    fn build_body_works() {
+        let pairs = |items: &[&str]| -> Vec<KVPair> { items.iter().map(|s| s.parse().unwrap()).collect() };
+        assert_eq!(
+            build_body(&pairs(&["user.name=alice", "user.age:=30", "items[1]=x"])).unwrap(),
+            json!({"user": {"name": "alice", "age": 30}, "items": [null, "x"]})
+        );
+        assert!(build_body(&pairs(&["user=alice", "user.name=bob"])).is_err());
+        assert!(build_body(&pairs(&["user.name=bob", "user=alice"])).is_err());
+        assert!(build_body(&pairs(&["items[0]=x", "items.a=y"])).is_err());
+        let err = build_body(&pairs(&["a[99999999999]=x"])).unwrap_err();
+        assert_eq!(err.to_string(), "Body key a[99999999999] has index 99999999999, more than the 1024 allowed");
+        assert_eq!(build_body(&pairs(&["a[1024]=x"])).unwrap()["a"].as_array().unwrap().len(), 1025);
+    }
+
+    #[test]
+    fn build_body_repeated_keys_works() {
+        let pairs = |items: &[&str]| -> Vec<KVPair> { items.iter().map(|s| s.parse().unwrap()).collect() };
+        assert_eq!(build_body(&pairs(&["tag=a"])).unwrap(), json!({"tag": "a"}));
+        assert_eq!(build_body(&pairs(&["tag=a", "tag=b"])).unwrap(), json!({"tag": ["a", "b"]}));
+        assert_eq!(
+            build_body(&pairs(&["tag=a", "n:=1", "tag=b", "tag:=true"])).unwrap(),
+            json!({"tag": ["a", "b", true], "n": 1})
+        );
+    }
+
+    /// Serves one connection, answering with the raw request it received.
+    async fn echo() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", n);
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&buf[..n]).await.unwrap();
+        });
+        format!("http://{}/", addr)
+    }
+
+    fn test_context(cli: &Cli) -> Context<'_> {
+        Context {
+            client: Client::new(),
+            headers: HeaderMap::new(),
+            overrides: header_overrides(cli).unwrap(),
+            auth: None,
+            netrc: None,
+            cache: None,
+            host_headers: Vec::new(),
+            cli,
+        }
+    }
+
+    #[tokio::test]
+    async fn host_header_works() {
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "--host-header", "example.com", "get", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get(&url)).unwrap();
+        let resp = ctx.client.execute(request).await.unwrap();
+        let wire = resp.text().await.unwrap();
+        assert!(wire.contains("host: example.com\r\n"));
+        assert!(!wire.contains("127.0.0.1"));
+    }
+
+    #[test]
+    fn netrc_auth_works() {
+        let cli = Cli::try_parse_from(["httpie", "get", "http://a.b"]).unwrap();
+        let ctx = Context {
+            netrc: Some(netrc::Netrc::parse("machine a.b login alice password s3cr3t").unwrap()),
+            ..test_context(&cli)
+        };
+        let request = build_request(&ctx, ctx.client.get("http://a.b/x")).unwrap();
+        assert_eq!(request.headers()[header::AUTHORIZATION], "Basic YWxpY2U6czNjcjN0");
+        let request = build_request(&ctx, ctx.client.get("http://c.d/x")).unwrap();
+        assert!(!request.headers().contains_key(header::AUTHORIZATION));
+
+        let ctx = Context {
+            auth: Some(("bob".into(), "pw".into())),
+            ..ctx
+        };
+        let request = build_request(&ctx, ctx.client.get("http://a.b/x")).unwrap();
+        assert_eq!(request.headers()[header::AUTHORIZATION], "Basic Ym9iOnB3");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn get_over_unix_socket_works() {
+        let path = std::env::temp_dir().join(format!("httpie-get-unix-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(buf[..n].starts_with(b"GET /containers/json HTTP/1.1\r\n"));
+            socket.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+        });
+
+        let socket = path.to_str().unwrap();
+        let url = "http://localhost/containers/json";
+        let cli = Cli::try_parse_from(["httpie", "--unix-socket", socket, "-q", "--expect-status", "200", "get", url]).unwrap();
+        let Command::Get(ref args) = cli.command else { unreachable!() };
+        let summary = get(&test_context(&cli), args).await.unwrap().unwrap();
+        assert_eq!(summary.status, StatusCode::NOT_FOUND);
+        let err = check_status(&cli, summary.status).unwrap_err();
+        assert_eq!(err.to_string(), "Expected status 200, got 404 Not Found");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn ws_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/chat", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let n = stream.peek(&mut buf).await.unwrap();
+            let handshake = String::from_utf8_lossy(&buf[..n]).into_owned();
+            assert!(handshake.contains("x-room: 42\r\n"));
+            assert!(handshake.contains("authorization: Basic YWxpY2U6cHc=\r\n"));
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let message = socket.next().await.unwrap().unwrap();
+            assert_eq!(message, ws::Message::Text("hello".into()));
+            socket.send(ws::Message::Binary(vec![1, 2, 3])).await.unwrap();
+            socket.send(message).await.unwrap();
+            socket.close(None).await.unwrap();
+        });
+        let cli = Cli::try_parse_from(["httpie", "-I", "-H", "X-Room: 42", "ws", &url, "-m", "hello"]).unwrap();
+        let Command::Ws(ref args) = cli.command else { unreachable!() };
+        let ctx = Context { auth: Some(("alice".into(), "pw".into())), ..test_context(&cli) };
+        ws(&ctx, args).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_returns_summary() {
+        let url = serve("HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: 8\r\n\r\n{\"id\":1}").await;
+        let cli = Cli::try_parse_from(["httpie", "--print", "b", "--color", "never", "post", &url, "name=alice"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        let summary = post(&test_context(&cli), Method::POST, args).await.unwrap().unwrap();
+        assert_eq!(summary.status, StatusCode::CREATED);
+        assert_eq!(summary.headers[header::CONTENT_TYPE], "application/json");
+        assert_eq!(summary.json().unwrap(), json!({"id": 1}));
+    }
+
+    #[tokio::test]
+    async fn streamed_summary_has_no_body() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello").await;
+        let cli = Cli::try_parse_from(["httpie", "--print", "b", "--color", "never", "get", &url]).unwrap();
+        let Command::Get(ref args) = cli.command else { unreachable!() };
+        let summary = get(&test_context(&cli), args).await.unwrap().unwrap();
+        assert_eq!(summary.status, StatusCode::OK);
+        assert!(summary.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn schema_works() {
+        let path = std::env::temp_dir().join(format!("httpie-schema-test-{}.json", std::process::id()));
+        let schema = json!({"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}});
+        std::fs::write(&path, schema.to_string()).unwrap();
+        let validator = load_schema(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 10\r\n\r\n{\"id\":\"1\"}").await;
+        let cli = Cli::try_parse_from(["httpie", "--print", "h", "--schema", "s.json", "--color", "never", "get", &url]).unwrap();
+        let Command::Get(ref args) = cli.command else { unreachable!() };
+        let summary = get(&test_context(&cli), args).await.unwrap().unwrap();
+        let body = summary.json().unwrap();
+        let error = validator.iter_errors(&body).next().unwrap();
+        assert_eq!(format_schema_error(&error), "/id: \"1\" is not of type \"integer\"");
+        assert_eq!(check_schema(&validator, &summary).unwrap_err().to_string(), "The response doesn't match the schema");
+        let valid = ResponseSummary { body: b"{\"id\":1}".to_vec(), ..summary };
+        assert!(check_schema(&validator, &valid).is_ok());
+        assert!(Cli::try_parse_from(["httpie", "-q", "--schema", "s.json", "get", "a.b"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn repeated_headers_work() {
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "-H", "X-Tag:a", "-H", "X-Tag:b", "-H", "Accept: text/csv", "get", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get(&url).header(header::ACCEPT, "*/*")).unwrap();
+        let wire = ctx.client.execute(request).await.unwrap().text().await.unwrap();
+        assert!(wire.contains("x-tag: a\r\nx-tag: b\r\n"));
+        assert!(wire.contains("accept: text/csv\r\n"));
+        assert!(!wire.contains("*/*"));
+    }
+
+    #[tokio::test]
+    async fn header_overrides_content_type_shortcut() {
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "-H", "Content-Type: text/csv", "post", &url, "--content-type", "json"]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.post(&url).header(header::CONTENT_TYPE, "application/json")).unwrap();
+        let wire = ctx.client.execute(request).await.unwrap().text().await.unwrap();
+        assert!(wire.contains("content-type: text/csv\r\n"));
+        assert!(!wire.contains("application/json"));
+    }
+
+    #[tokio::test]
+    async fn max_time_works() {
+        let cli = Cli::try_parse_from(["httpie", "--max-time", "0.01", "get", "http://a.b"]).unwrap();
+        assert_eq!(cli.max_time, Some(Duration::from_millis(10)));
+        assert!(Cli::try_parse_from(["httpie", "--max-time", "-1", "get", "http://a.b"]).is_err());
+        assert!(tokio::time::timeout(Duration::from_secs(1), deadline(cli.max_time)).await.is_ok());
+        assert!(tokio::time::timeout(Duration::from_millis(20), deadline(None)).await.is_err());
+    }
+
+    #[test]
+    fn timeout_args_work() {
+        let cli = Cli::try_parse_from(["httpie", "--timeout", "30", "--connect-timeout", "2.5", "get", "http://a.b"]).unwrap();
+        assert_eq!((cli.timeout, cli.connect_timeout), (Some(Duration::from_secs(30)), Some(Duration::from_millis(2500))));
+        let err = Cli::try_parse_from(["httpie", "--connect-timeout", "nan", "get", "http://a.b"]).unwrap_err();
+        assert!(err.to_string().contains("--connect-timeout"), "{}", err);
+        assert!(Cli::try_parse_from(["httpie", "--timeout", "-1", "get", "http://a.b"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn digest_auth_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/dir?a=1", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0; 4096];
+                    while let Ok(n @ 1..) = socket.read(&mut buf).await {
+                        let request = String::from_utf8_lossy(&buf[..n]).into_owned();
+                        let response = match request.contains("authorization: Digest") {
+                            true => format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", n, request),
+                            false => "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Digest realm=\"r\", qop=\"auth\", nonce=\"n1\"\r\nContent-Length: 0\r\n\r\n".into(),
+                        };
+                        socket.write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        let cli = Cli::try_parse_from(["httpie", "--auth", "alice:pw", "--auth-type", "digest", "get", &url]).unwrap();
+        let ctx = Context { auth: Some(("alice".into(), "pw".into())), ..test_context(&cli) };
+        let request = build_request(&ctx, ctx.client.get(&url)).unwrap();
+        assert!(!request.headers().contains_key(header::AUTHORIZATION));
+        let response = exchange(&ctx, request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let wire = response.text().await.unwrap();
+        assert!(wire.contains(r#"username="alice", realm="r", nonce="n1", uri="/dir?a=1""#));
+        assert!(wire.contains("qop=auth, nc=00000001"));
+    }
+
+    #[test]
+    fn keepalive_flags_work() {
+        let cli = Cli::try_parse_from(["httpie", "--pool-max-idle", "2", "get", "http://a.b"]).unwrap();
+        assert_eq!(cli.pool_max_idle, Some(2));
+        assert!(Cli::try_parse_from(["httpie", "--no-keepalive", "--pool-max-idle", "2", "get", "http://a.b"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn url_credentials_work() {
+        let url = echo().await.replace("http://", "http://al%40ice:p%3Aw@");
+        let cli = Cli::try_parse_from(["httpie", "get", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get(&url)).unwrap();
+        assert!(!request.url().as_str().contains('@'));
+        let wire = ctx.client.execute(request).await.unwrap().text().await.unwrap();
+        assert!(wire.starts_with("GET / HTTP/1.1\r\n"));
+        assert!(!wire.contains("al%40ice"));
+        // base64 of `al@ice:p:w`.
+        assert!(wire.contains("authorization: Basic YWxAaWNlOnA6dw==\r\n"));
+
+        let cli = Cli::try_parse_from(["httpie", "get", "http://a.b"]).unwrap();
+        let ctx = Context { auth: Some(("bob".into(), "pw".into())), ..test_context(&cli) };
+        let request = build_request(&ctx, ctx.client.get("http://u:p@a.b/")).unwrap();
+        assert_eq!(request.url().as_str(), "http://a.b/");
+        let values: Vec<_> = request.headers().get_all(header::AUTHORIZATION).iter().collect();
+        assert_eq!(values, ["Basic Ym9iOnB3"]);
+
+        let ctx = Context {
+            netrc: Some(netrc::Netrc::parse("machine a.b login alice password s3cr3t").unwrap()),
+            ..test_context(&cli)
+        };
+        let request = build_request(&ctx, ctx.client.get("http://u:p@a.b/")).unwrap();
+        let values: Vec<_> = request.headers().get_all(header::AUTHORIZATION).iter().collect();
+        assert_eq!(values, ["Basic dTpw"]);
+    }
+
+    #[test]
+    fn parse_body_item_works() {
+        assert_eq!(parse_body_item("email=a@b.c").unwrap(), BodyItem::Field(parse_kv_pair("email=a@b.c").unwrap()));
+        let BodyItem::File(file) = parse_body_item("pic@a.jpg;type=image/png").unwrap() else { panic!() };
+        assert_eq!((file.field.as_str(), file.path.as_path(), file.mime), ("pic", Path::new("a.jpg"), Some(mime::IMAGE_PNG)));
+        let BodyItem::File(file) = parse_body_item("doc@dir/a=b.txt").unwrap() else { panic!() };
+        assert_eq!((file.path.as_path(), file.mime), (Path::new("dir/a=b.txt"), None));
+        assert!(parse_body_item("@a.txt").is_err());
+        assert!(parse_body_item("pic@a.jpg;type=nope").is_err());
+        let err = parse_body_item("pic@/no/such/file").map(|item| match item {
+            BodyItem::File(file) => file.part().unwrap_err().to_string(),
+            BodyItem::Field(_) => unreachable!(),
+        });
+        assert!(err.unwrap().starts_with("Failed to read /no/such/file for field pic:"));
+    }
+
+    #[tokio::test]
+    async fn multipart_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut wire = Vec::new();
+            let mut buf = [0; 4096];
+            while !wire.ends_with(b"--\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                wire.extend_from_slice(&buf[..n]);
+            }
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", wire.len());
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&wire).await.unwrap();
+        });
+        let file_name = format!("httpie-multipart-test-{}.txt", std::process::id());
+        let path = std::env::temp_dir().join(&file_name);
+        std::fs::write(&path, "hello").unwrap();
+        let logo = format!("logo@{};type=image/png", path.display());
+        let doc = format!("doc@{}", path.display());
+        let cli = Cli::try_parse_from(["httpie", "post", &url, "name=alice", &logo, &doc]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.post(&url).multipart(build_multipart(&ctx, &args.body).unwrap())).unwrap();
+        let wire = ctx.client.execute(request).await.unwrap().text().await.unwrap();
+        assert!(wire.contains("content-type: multipart/form-data; boundary="));
+        let name = wire.find("name=\"name\"\r\n\r\nalice").unwrap();
+        let logo = wire.find(&format!("name=\"logo\"; filename=\"{}\"\r\nContent-Type: image/png\r\n\r\nhello", file_name)).unwrap();
+        let doc = wire.find(&format!("name=\"doc\"; filename=\"{}\"\r\nContent-Type: text/plain\r\n\r\nhello", file_name)).unwrap();
+        assert!(name < logo && logo < doc);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_post_has_no_body() {
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        let request = post_request(&test_context(&cli), Method::POST, args).unwrap().build().unwrap();
+        assert!(request.body().is_none());
+        assert!(!request.headers().contains_key(header::CONTENT_TYPE));
+
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b", "a=1"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        let request = post_request(&test_context(&cli), Method::POST, args).unwrap().build().unwrap();
+        assert_eq!(request.body().unwrap().as_bytes().unwrap(), b"{\"a\":\"1\"}");
+    }
+
+    #[tokio::test]
+    async fn multipart_flag_works() {
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "post", "--multipart", &url, "name=alice", "team=api"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert!(args.multipart && !args.has_files());
+        assert!(Cli::try_parse_from(["httpie", "post", "--multipart", "--form", &url]).is_err());
+        let ctx = test_context(&cli);
+        let request = post_request(&ctx, Method::POST, args).unwrap().build().unwrap();
+        assert!(request.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("multipart/form-data; boundary="));
+        let wire = ctx.client.execute(request).await.unwrap().text().await.unwrap();
+        assert!(wire.contains("content-type: multipart/form-data"));
+    }
+
+    #[test]
+    fn request_wait_works() {
+        let delay = Duration::from_millis(100);
+        let started = Instant::now();
+        assert_eq!(request_wait(delay, 0, 1, started), None);
+        assert_eq!(request_wait(delay, 3, 1, started), Some(delay));
+        let wait = request_wait(delay, 3, 4, started).unwrap();
+        assert!(wait > Duration::from_millis(250) && wait <= Duration::from_millis(300));
+        assert_eq!(request_wait(delay, 1, 4, started - delay * 2), None);
+        assert!(Cli::try_parse_from(["httpie", "--delay", "10", "get", "http://a.b"]).is_err());
+    }
+
+    #[test]
+    fn options_summary_works() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ALLOW, HeaderValue::from_static("GET,POST, OPTIONS"));
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("content-type"));
+        headers.append(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("authorization"));
+        assert_eq!(
+            options_summary(&headers),
+            [
+                "Allowed methods: GET, POST, OPTIONS",
+                "CORS:",
+                "  allow headers: content-type, authorization",
+                "  allow origin: *",
+            ]
+        );
+        assert_eq!(options_summary(&HeaderMap::new()), ["No Allow or Access-Control-* headers in the response"]);
+    }
+
+    #[tokio::test]
+    async fn options_works() {
+        let url = serve("HTTP/1.1 204 No Content\r\nAllow: GET, HEAD\r\n\r\n").await;
+        let cli = Cli::try_parse_from(["httpie", "--print", "", "options", &url]).unwrap();
+        let Command::Options(ref args) = cli.command else { unreachable!() };
+        let summary = options(&test_context(&cli), args).await.unwrap().unwrap();
+        assert_eq!(summary.status, StatusCode::NO_CONTENT);
+        assert!(summary.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn max_per_host_works() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (active, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (active_, most_) = (active.clone(), most.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let (active, most) = (active_.clone(), most_.clone());
+                tokio::spawn(async move {
+                    let mut buf = [0; 4096];
+                    let _ = socket.read(&mut buf).await.unwrap();
+                    most.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    socket.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+                });
+            }
+        });
+        let cli = Cli::try_parse_from(["httpie", "-q", "--max-per-host", "1", "get", &url, &url, &url]).unwrap();
+        let Command::Get(ref args) = cli.command else { unreachable!() };
+        get(&test_context(&cli), args).await.unwrap();
+        assert_eq!(most.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_works() {
+        let limiter = RateLimiter::new(Duration::from_millis(50));
+        let started = Instant::now();
+        futures_util::future::join_all((0..4).map(|_| limiter.acquire())).await;
+        let elapsed = started.elapsed();
+        // The first request starts at once and the other three 50ms apart.
+        assert!(elapsed >= Duration::from_millis(150) && elapsed < Duration::from_millis(300), "{:?}", elapsed);
+        assert!(parse_rps("0").is_err());
+        assert!(parse_rps("fast").is_err());
+        assert_eq!(parse_rps("2.5").unwrap(), Duration::from_millis(400));
+        assert!(parse_rps("1e-20").is_err());
+        assert!(Cli::try_parse_from(["httpie", "--rate-limit", "1e-20", "get", "http://a.b"]).is_err());
+    }
+
+    #[test]
+    fn host_headers_work() {
+        let cli = Cli::try_parse_from(["httpie", "-H", "X-Team: cli", "get", "http://a.b"]).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-env", HeaderValue::from_static("staging"));
+        headers.insert("x-team", HeaderValue::from_static("config"));
+        let ctx = Context { host_headers: vec![("*.staging.example.com".into(), headers)], ..test_context(&cli) };
+        let request = build_request(&ctx, ctx.client.get("http://api.staging.example.com/")).unwrap();
+        assert_eq!(request.headers()["x-env"], "staging");
+        assert_eq!(request.headers()["x-team"], "cli");
+        let request = build_request(&ctx, ctx.client.get("http://api.example.com/")).unwrap();
+        assert!(!request.headers().contains_key("x-env"));
+    }
+
+    #[test]
+    fn read_raw_body_works() {
+        let path = std::env::temp_dir().join(format!("httpie-body-file-test-{}", std::process::id()));
+        std::fs::write(&path, "raw").unwrap();
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b", "--body-file", path.to_str().unwrap()]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert_eq!(read_raw_body(&test_context(&cli), args).unwrap(), Some(b"raw".to_vec()));
+        std::fs::remove_file(&path).unwrap();
+
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b", "--body-file", "-"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert!(read_raw_body(&test_context(&cli), args).is_err());
+
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert_eq!(read_raw_body(&test_context(&cli), args).unwrap(), None);
+
+        // Without the feature, or without a desktop session, there's no clipboard to read.
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b", "--body-clipboard"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert!(read_raw_body(&test_context(&cli), args).is_err());
+        assert!(Cli::try_parse_from(["httpie", "post", "http://a.b", "a=1", "--body-clipboard"]).is_err());
+
+        let cli = Cli::try_parse_from(["httpie", "-I", "post", "http://a.b", "--raw", "{\"a\": 1} "]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert_eq!(read_raw_body(&test_context(&cli), args).unwrap(), Some(b"{\"a\": 1} ".to_vec()));
+        assert!(Cli::try_parse_from(["httpie", "post", "http://a.b", "a=1", "--raw", "x"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn get_several_urls_works() {
+        let ok = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+        let cli = Cli::try_parse_from(["httpie", "--print", "", "get", &ok, "http://127.0.0.1:1/"]).unwrap();
+        let Command::Get(ref args) = cli.command else { unreachable!() };
+        let err = get(&test_context(&cli), args).await.unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 requests failed");
+
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+        let resp = buffer_response(Client::new().get(&url).send().await.unwrap()).await.unwrap();
+        assert_eq!(resp.url().as_str(), url);
+        assert_eq!(resp.text().await.unwrap(), "ok");
+    }
+
+    #[test]
+    fn latency_stats_works() {
+        assert_eq!(LatencyStats::new(&mut []), None);
+        let mut latencies: Vec<_> = (1..=20).rev().map(Duration::from_millis).collect();
+        assert_eq!(
+            LatencyStats::new(&mut latencies).unwrap(),
+            LatencyStats {
+                min: Duration::from_millis(1),
+                max: Duration::from_millis(20),
+                mean: Duration::from_micros(10500),
+                p50: Duration::from_millis(10),
+                p95: Duration::from_millis(19),
+            }
+        );
+        let one = LatencyStats::new(&mut [Duration::from_millis(5)]).unwrap();
+        assert_eq!((one.p50, one.p95), (Duration::from_millis(5), Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn status_matcher_works() {
+        for bad in ["abc", "6xx", "0xx", "99", "2x", "1000"] {
+            assert!(parse_status_matcher(bad).is_err(), "{}", bad);
+        }
+        let class = parse_status_matcher("2XX").unwrap();
+        assert_eq!(class, StatusMatcher::Class(2));
+        assert!(class.matches(StatusCode::NO_CONTENT));
+        assert!(!class.matches(StatusCode::NOT_FOUND));
+        assert!(parse_status_matcher("404").unwrap().matches(StatusCode::NOT_FOUND));
+
+        let cli = Cli::try_parse_from(["httpie", "--expect-status", "2xx", "get", "http://a.b"]).unwrap();
+        assert_eq!(
+            check_status(&cli, StatusCode::NOT_FOUND).unwrap_err().to_string(),
+            "Expected status 2xx, got 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn parse_range_works() {
+        for bad in ["", "-", "a-b", "10-5", "5"] {
+            assert!(parse_range(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(parse_range("0-1023").unwrap().to_string(), "bytes=0-1023");
+        assert_eq!(parse_range("500-").unwrap().to_string(), "bytes=500-");
+        assert_eq!(parse_range("-500").unwrap().to_string(), "bytes=-500");
+        assert_eq!(parse_range("100-1023").unwrap().resume(500), Some(ByteRange::From(600, Some(1023))));
+        assert_eq!(parse_range("0-").unwrap().resume(500), Some(ByteRange::From(500, None)));
+        assert_eq!(parse_range("0-9").unwrap().resume(11), None);
+        assert_eq!(parse_range("-500").unwrap().resume(1), None);
+    }
+
+    #[test]
+    fn resume_range_works() {
+        let path = std::env::temp_dir().join(format!("httpie-range-test-{}", std::process::id()));
+        std::fs::write(&path, "12345").unwrap();
+        let url: Url = "http://a.b/f".parse().unwrap();
+        let path_arg = path.to_str().unwrap();
+        let cli = Cli::try_parse_from(["httpie", "--range", "0-", "-o", path_arg, "get", "http://a.b/f"]).unwrap();
+        assert_eq!(resume_range(&cli, &url).unwrap(), Some((path.clone(), ByteRange::From(5, None))));
+        let cli = Cli::try_parse_from(["httpie", "--range", "0-4", "-o", path_arg, "get", "http://a.b/f"]).unwrap();
+        assert!(resume_range(&cli, &url).is_err());
+        let cli = Cli::try_parse_from(["httpie", "-o", path_arg, "get", "http://a.b/f"]).unwrap();
+        assert_eq!(resume_range(&cli, &url).unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn conditional_headers_work() {
+        assert!(parse_http_date("yesterday").is_err());
+        assert_eq!(parse_etag("abc").unwrap(), "\"abc\"");
+        assert_eq!(parse_etag("W/\"abc\"").unwrap(), "W/\"abc\"");
+        assert_eq!(parse_etag("*").unwrap(), "*");
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let cli = Cli::try_parse_from(["httpie", "--if-modified-since", date, "--if-none-match", "v1", "get", "http://a.b"]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get("http://a.b")).unwrap();
+        assert_eq!(request.headers()[header::IF_MODIFIED_SINCE], date);
+        assert_eq!(request.headers()[header::IF_NONE_MATCH], "\"v1\"");
+        let cli = Cli::try_parse_from(["httpie", "--etag", "v2", "get", "http://a.b"]).unwrap();
+        assert_eq!(cli.if_none_match.unwrap(), "\"v2\"");
+    }
+
+    #[tokio::test]
+    async fn not_modified_keeps_output_file() {
+        let path = std::env::temp_dir().join(format!("httpie-not-modified-test-{}", std::process::id()));
+        std::fs::write(&path, "cached").unwrap();
+        let url = serve("HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n").await;
+        let cli = Cli::try_parse_from(["httpie", "-o", path.to_str().unwrap(), "get", &url]).unwrap();
+        print_resp(Client::new().get(&url).send().await.unwrap(), &cli, None).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"cached");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn output_headers_works() {
+        let body = std::env::temp_dir().join(format!("httpie-output-headers-body-{}", std::process::id()));
+        let headers = std::env::temp_dir().join(format!("httpie-output-headers-{}", std::process::id()));
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello").await;
+        let (body_arg, headers_arg) = (body.to_str().unwrap(), headers.to_str().unwrap());
+        let cli = Cli::try_parse_from(["httpie", "-q", "-o", body_arg, "--output-headers", headers_arg, "get", &url]).unwrap();
+        print_resp(Client::new().get(&url).send().await.unwrap(), &cli, None).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&body).unwrap(), "hello");
+        assert_eq!(
+            std::fs::read_to_string(&headers).unwrap(),
+            "HTTP/1.1 200 OK\ncontent-type: text/plain\ncontent-length: 5\n\n"
+        );
+        std::fs::remove_file(&body).unwrap();
+        std::fs::remove_file(&headers).unwrap();
+    }
+
+    #[tokio::test]
+    async fn append_works() {
+        let path = std::env::temp_dir().join(format!("httpie-append-test-{}", std::process::id()));
+        std::fs::write(&path, "one\n").unwrap();
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ntwo\n").await;
+        let cli = Cli::try_parse_from(["httpie", "-q", "-o", path.to_str().unwrap(), "--append", "--append-stamp", "get", &url]).unwrap();
+        print_resp(Client::new().get(&url).send().await.unwrap(), &cli, None).await.unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let (stamp, two) = text.strip_prefix("one\n=== ").unwrap().split_once(" ===\n").unwrap();
+        assert!(stamp.ends_with(&format!("GMT {}", url)));
+        assert_eq!(two, "two\n");
+        std::fs::remove_file(&path).unwrap();
+        assert!(Cli::try_parse_from(["httpie", "--append", "get", "http://a.b"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn truncated_body_is_kept() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello").await;
+        let mut resp = Client::new().get(&url).send().await.unwrap();
+        assert_eq!(read_body(&mut resp, None).await.unwrap(), (b"hello".to_vec(), false));
+    }
+
+    #[tokio::test]
+    async fn request_id_works() {
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "--request-id", "get", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get(&url)).unwrap();
+        let id = request.headers()[X_REQUEST_ID].to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        let wire = ctx.client.execute(request).await.unwrap().text().await.unwrap();
+        assert!(wire.contains(&format!("x-request-id: {}\r\n", id)));
+
+        let cli = Cli::try_parse_from(["httpie", "--request-id", "-H", "X-Request-ID: mine", "get", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get(&url)).unwrap();
+        assert_eq!(request.headers()[X_REQUEST_ID], "mine");
+    }
+
+    #[tokio::test]
+    async fn session_read_only_works() {
+        let path = std::env::temp_dir().join(format!("httpie-session-read-only-{}.json", std::process::id()));
+        let path_arg = path.to_str().unwrap();
+        let url = serve("HTTP/1.1 200 OK\r\nSet-Cookie: sid=abc\r\nContent-Length: 0\r\n\r\n").await;
+        let cli = Cli::try_parse_from(["httpie", "-q", "-H", "X-Team: api", "--session", path_arg, "get", &url]).unwrap();
+        let Command::Get(ref args) = cli.command else { unreachable!() };
+        get(&test_context(&cli), args).await.unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("\"sid\": \"abc\"") && saved.contains("\"x-team\": \"api\""));
+
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "-q", "-H", "X-Env: dev", "--session-read-only", path_arg, "get", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.get(&url)).unwrap();
+        assert_eq!(request.headers()[header::COOKIE], "sid=abc");
+        assert_eq!(request.headers()["x-team"], "api");
+        send(&ctx, ctx.client.get(&url)).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), saved);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn fetch_and_graphql_update_the_session() {
+        let path = std::env::temp_dir().join(format!("httpie-session-exchange-{}.json", std::process::id()));
+        let path_arg = path.to_str().unwrap();
+        let url = serve("HTTP/1.1 200 OK\r\nSet-Cookie: sid=abc\r\nContent-Length: 0\r\n\r\n").await;
+        let cli = Cli::try_parse_from(["httpie", "-q", "--session", path_arg, "get", &url]).unwrap();
+        fetch(&test_context(&cli), &url).await.unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"sid\": \"abc\""));
+
+        let body = r#"{"data":null,"errors":[{"message":"nope"}]}"#;
+        let url = serve(Box::leak(format!("HTTP/1.1 200 OK\r\nSet-Cookie: sid=def\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).into_boxed_str())).await;
+        let cli = Cli::try_parse_from(["httpie", "-q", "--session", path_arg, "graphql", &url, "{ me }"]).unwrap();
+        let Command::Graphql(ref args) = cli.command else { unreachable!() };
+        let summary = graphql(&test_context(&cli), args).await.unwrap().unwrap();
+        assert_eq!(summary.body, body.as_bytes());
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"sid\": \"def\""));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_header_works() {
+        assert!(parse_header("X-Tag").is_err());
+        assert!(parse_header("Bad Name: x").is_err());
+        let pair = parse_header("X-Tag: a:b ").unwrap();
+        assert_eq!((pair.name.as_str(), pair.value.as_str()), ("x-tag", "a:b"));
+    }
+
+    #[test]
+    fn headers_from_file_works() {
+        let pairs = parse_headers_file("# auth\nX-Token: a\n\n  X-Env: staging\n").unwrap();
+        assert_eq!(pairs.iter().map(|p| p.value.as_str()).collect::<Vec<_>>(), ["a", "staging"]);
+        let err = parse_headers_file("X-Token: a\nnope\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: Failed to parse header nope");
+
+        let path = std::env::temp_dir().join(format!("httpie-headers-file-test-{}", std::process::id()));
+        std::fs::write(&path, "X-Token: file\nX-Env: staging\n").unwrap();
+        for flag in ["--headers-from", "--headers-file"] {
+            let cli = Cli::try_parse_from(["httpie", flag, path.to_str().unwrap(), "-H", "X-Token: cli", "get", "http://a.b"]).unwrap();
+            let headers = header_overrides(&cli).unwrap();
+            assert_eq!(headers["x-token"], "cli");
+            assert_eq!(headers["x-env"], "staging");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn expand_env_works() {
+        std::env::set_var("HTTPIE_TEST_TOKEN", "s3cr3t");
+        let strict = ExpandMode::Strict;
+        assert_eq!(expand_env("Bearer $HTTPIE_TEST_TOKEN!", strict).unwrap(), "Bearer s3cr3t!");
+        assert_eq!(expand_env("${HTTPIE_TEST_TOKEN}x $$5 $ $1", strict).unwrap(), "s3cr3tx $5 $ $1");
+        assert!(expand_env("$HTTPIE_TEST_MISSING", strict).is_err());
+        assert_eq!(
+            expand_env("a${HTTPIE_TEST_MISSING}b $HTTPIE_TEST_MISSING", ExpandMode::Lenient).unwrap(),
+            "a${HTTPIE_TEST_MISSING}b $HTTPIE_TEST_MISSING"
+        );
+        assert_eq!(expand_env("a${HTTPIE_TEST_MISSING}b", ExpandMode::Empty).unwrap(), "ab");
+        let pair = parse_kv_pair(r#"t:={"a": ["$HTTPIE_TEST_TOKEN", 1]}"#).unwrap();
+        assert_eq!(pair.expand_env(strict).unwrap().v, json!({"a": ["s3cr3t", 1]}));
+
+        let cli = Cli::try_parse_from(["httpie", "--expand-env", "-H", "X-Token:$HTTPIE_TEST_TOKEN", "get", "http://a.b"]).unwrap();
+        assert_eq!(header_overrides(&cli).unwrap()["x-token"], "s3cr3t");
+    }
+
+    #[test]
+    fn expand_mode_works() {
+        std::env::set_var("HTTPIE_TEST_USER", "alice");
+        let headers = |flags: &[&str]| {
+            let args = [&["httpie"], flags, &["-H", "X-User:$HTTPIE_TEST_USER$HTTPIE_TEST_UNSET", "get", "http://a.b"]].concat();
+            header_overrides(&Cli::try_parse_from(args).unwrap())
+        };
+        // Expanded by default, with unset variables dropped.
+        assert_eq!(headers(&[]).unwrap()["x-user"], "alice");
+        assert_eq!(headers(&["--no-env-expand"]).unwrap()["x-user"], "$HTTPIE_TEST_USER$HTTPIE_TEST_UNSET");
+        assert_eq!(headers(&["--strict-env"]).unwrap_err().to_string(), "Environment variable HTTPIE_TEST_UNSET is not set");
+        assert!(Cli::try_parse_from(["httpie", "--no-env-expand", "--strict-env", "get", "http://a.b"]).is_err());
+
+        let cli = Cli::try_parse_from(["httpie", "post", "http://a.b", "user=$HTTPIE_TEST_USER"]).unwrap();
+        let Command::Post(ref args) = cli.command else { unreachable!() };
+        assert_eq!(test_context(&cli).pairs(&args.fields()).unwrap()[0].v, json!("alice"));
+    }
+
+    #[test]
+    fn build_form_works() {
+        let pairs: Vec<KVPair> = ["a=1", "b=2"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(build_form(&pairs).unwrap(), vec![("a", "1"), ("b", "2")]);
+        assert!(build_form(&[parse_kv_pair("a:=1").unwrap()]).is_err());
+    }
+
+    #[test]
+    fn build_form_repeated_keys_works() {
+        let pairs: Vec<KVPair> = ["tag=a", "x=1", "tag=b"].iter().map(|s| s.parse().unwrap()).collect();
+        let request = Client::new().post("http://a.b").form(&build_form(&pairs).unwrap()).build().unwrap();
+        assert_eq!(request.body().unwrap().as_bytes().unwrap(), b"tag=a&x=1&tag=b");
+    }
+
+    #[test]
+    fn graphql_works() {
+        let query = "query($id: ID) { user(id: $id) { name } }";
+        let cli = Cli::try_parse_from(["httpie", "graphql", "http://a.b", query, "id:=7", "--operation", "One"]).unwrap();
+        let Command::Graphql(ref args) = cli.command else { unreachable!() };
+        assert_eq!(
+            graphql_envelope(&test_context(&cli), args).unwrap(),
+            json!({"query": query, "variables": {"id": 7}, "operationName": "One"})
+        );
+        let path = std::env::temp_dir().join(format!("httpie-graphql-test-{}", std::process::id()));
+        std::fs::write(&path, "{ me { id } }").unwrap();
+        let file = format!("@{}", path.display());
+        let cli = Cli::try_parse_from(["httpie", "graphql", "http://a.b", &file]).unwrap();
+        let Command::Graphql(ref args) = cli.command else { unreachable!() };
+        assert_eq!(graphql_envelope(&test_context(&cli), args).unwrap(), json!({"query": "{ me { id } }", "variables": {}}));
+        std::fs::remove_file(&path).unwrap();
+
+        let body = br#"{"data": null, "errors": [{"message": "boom", "path": ["user", 0, "name"]}, {"message": "bad"}]}"#;
+        assert_eq!(graphql_errors(body), ["boom (at user.0.name)", "bad"]);
+        assert!(graphql_errors(br#"{"data": {"user": null}}"#).is_empty());
+        assert!(graphql_errors(b"not json").is_empty());
+    }
+
+    #[tokio::test]
+    async fn compress_works() {
+        let big = format!("a={}", "x".repeat(COMPRESS_THRESHOLD));
+        let cli = Cli::try_parse_from(["httpie", "--compress", "post", "http://a.b", &big]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.post("http://a.b").body(big.clone())).unwrap();
+        assert_eq!(request.headers()[header::CONTENT_ENCODING], "gzip");
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(request.body().unwrap().as_bytes().unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, big);
+        let (text, readable) = request_body_text(&request).unwrap();
+        assert!(text.ends_with("bytes, gzip-encoded]") && !readable);
+
+        let request = build_request(&ctx, ctx.client.post("http://a.b").body("small")).unwrap();
+        assert!(!request.headers().contains_key(header::CONTENT_ENCODING));
+
+        let url = echo().await;
+        let cli = Cli::try_parse_from(["httpie", "--compress=always", "post", &url]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.post(&url).body("small")).unwrap();
+        let wire = ctx.client.execute(request).await.unwrap().bytes().await.unwrap();
+        assert!(wire.windows(22).any(|w| w == b"content-encoding: gzip"));
+        assert!(!wire.ends_with(b"small"));
+
+        let cli = Cli::try_parse_from(["httpie", "--compress", "--compress-with", "br", "post", "http://a.b"]).unwrap();
+        let ctx = test_context(&cli);
+        let request = build_request(&ctx, ctx.client.post("http://a.b").body(big.clone())).unwrap();
+        assert_eq!(request.headers()[header::CONTENT_ENCODING], "br");
+        assert_eq!(encoding::decode("br", request.body().unwrap().as_bytes().unwrap()).unwrap(), big.as_bytes());
+        assert!(Cli::try_parse_from(["httpie", "--compress-with", "br", "post", "http://a.b"]).is_err());
+    }
+
+    #[test]
+    fn post_args_work() {
+        assert!(Cli::try_parse_from(["httpie", "post", "http://a.b", "--form", "--content-type", "text/plain"]).is_err());
+        assert!(Cli::try_parse_from(["httpie", "post", "http://a.b", "--content-type", "nope"]).is_err());
+        assert!(Cli::try_parse_from(["httpie", "post", "http://a.b", "--content-type", "application/vnd.api+json"]).is_ok());
+        assert_eq!(parse_mime("text").unwrap(), mime::TEXT_PLAIN);
+        assert!(Cli::try_parse_from(["httpie", "put", "http://a.b", "a=1", "--body-env", "BODY"]).is_err());
+        assert!(Cli::try_parse_from(["httpie", "patch", "http://a.b", "--body-env", "BODY"]).is_ok());
+    }
+
+    #[test]
+    fn parse_auth_works() {
+        assert!(parse_auth(":secret").is_err());
+        assert_eq!(
+            parse_auth("alice:se:cret").unwrap(),
+            Auth {
+                user: "alice".into(),
+                password: Some("se:cret".into()),
+            }
+        );
+        assert_eq!(parse_auth("alice").unwrap().password, None);
+        assert_eq!(parse_auth("alice:").unwrap().resolve().unwrap(), ("alice".into(), "".into()));
+    }
+
+    #[test]
+    fn parse_resolve_works() {
+        assert!(parse_resolve("example.com:443").is_err());
+        assert!(parse_resolve(":443:10.0.0.5").is_err());
+        assert!(parse_resolve("example.com:https:10.0.0.5").is_err());
+        assert!(parse_resolve("example.com:443:10.0.0").is_err());
+        assert_eq!(
+            parse_resolve("example.com:443:10.0.0.5").unwrap(),
+            Resolve {
+                host: "example.com".into(),
+                addr: "10.0.0.5:443".parse().unwrap(),
+            }
+        );
+        assert_eq!(parse_resolve("example.com:80:[::1]").unwrap().addr, "[::1]:80".parse().unwrap());
+        assert_eq!(
+            parse_resolve("example.com:443:10.0.0").unwrap_err().to_string(),
+            "Invalid IP address 10.0.0 in resolve example.com:443:10.0.0"
+        );
+        assert_eq!(
+            parse_resolve("example.com").unwrap_err().to_string(),
+            "Failed to parse resolve example.com, expected HOST:PORT:ADDR"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_works() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+        let port = url.trim_end_matches('/').rsplit(':').next().unwrap();
+        let resolve = parse_resolve(&format!("api.example.invalid:{}:127.0.0.1", port)).unwrap();
+        let client = Client::builder().resolve(&resolve.host, resolve.addr).build().unwrap();
+        let response = client.get(format!("http://api.example.invalid:{}/", port)).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[test]
+    fn accept_header_works() {
+        let accept = |args: &[&str]| accept_header(&Cli::try_parse_from(args).unwrap());
+        assert_eq!(accept(&["httpie", "get", "http://a.b"]), None);
+        assert_eq!(accept(&["httpie", "--json", "get", "http://a.b"]).unwrap(), "application/json, */*;q=0.5");
+        assert_eq!(accept(&["httpie", "get", "http://a.b", "--accept", "text/csv"]).unwrap(), "text/csv");
+        assert_eq!(accept(&["httpie", "get", "http://a.b", "--accept", "JSON"]).unwrap(), "application/json");
+        assert!(Cli::try_parse_from(["httpie", "--json", "--xml", "get", "http://a.b"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn describe_error_works() {
+        let err = Client::new().get("http://127.0.0.1:1/").send().await.unwrap_err();
+        assert_eq!(describe_error(&err.into()).unwrap(), "could not connect to 127.0.0.1:1");
+        assert_eq!(describe_error(&anyhow!("plain")), None);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        let client = Client::builder().timeout(Duration::from_millis(50)).build().unwrap();
+        let err = client.get(format!("http://{}/", addr)).send().await.unwrap_err();
+        assert_eq!(describe_error(&err.into()).unwrap(), format!("{} did not respond in time", addr));
+        assert_eq!(limit_text(Some((Duration::from_millis(2500), "timeout in the config")), ""), "within the 2.5s timeout in the config");
+    }
+
+    #[tokio::test]
+    async fn error_kind_works() {
+        let err = Client::new().get("http://127.0.0.1:1/").send().await.unwrap_err();
+        assert_eq!(error_kind(&err.into()), "network");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        let client = Client::builder().timeout(Duration::from_millis(50)).build().unwrap();
+        let err = client.get(format!("http://{}/", addr)).send().await.unwrap_err();
+        assert_eq!(error_kind(&err.into()), "timeout");
+        let cli = Cli::try_parse_from(["httpie", "--expect-status", "2xx", "get", "http://a.b"]).unwrap();
+        assert_eq!(error_kind(&check_status(&cli, StatusCode::NOT_FOUND).unwrap_err()), "http_status");
+        assert_eq!(error_kind(&serde_json::from_str::<Value>("{").unwrap_err().into()), "parse");
+        assert_eq!(error_kind(&anyhow!("plain")), "other");
+    }
+
+    #[tokio::test]
+    async fn exit_code_works() {
+        let err = Client::new().get("http://127.0.0.1:1/").send().await.unwrap_err();
+        assert_eq!(exit_code(&err.into()), EXIT_CONNECT);
+        let err = Client::new().get("http://nonexistent.invalid/").send().await.unwrap_err();
+        assert_eq!(exit_code(&err.into()), EXIT_DNS);
+        let cli = Cli::try_parse_from(["httpie", "--expect-status", "2xx", "get", "http://a.b"]).unwrap();
+        assert_eq!(exit_code(&check_status(&cli, StatusCode::NOT_FOUND).unwrap_err()), 1);
+        assert_eq!(exit_code(&anyhow!("plain")), 1);
+    }
+
+    #[tokio::test]
+    async fn diff_works() {
+        colored::control::set_override(false);
+        assert_eq!(unified_diff("a\n", "a\n", "l", "r"), None);
+        assert_eq!(unified_diff("a\nb\n", "a\nc\n", "l", "r").unwrap(), "--- l\n+++ r\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n");
+
+        let left = serve("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 16\r\n\r\n{\"b\": 1, \"a\": 2}").await;
+        let right = serve("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 13\r\n\r\n{\"a\":2,\"b\":1}").await;
+        let cli = Cli::try_parse_from(["httpie", "diff", &left, &right]).unwrap();
+        let Command::Diff(ref args) = cli.command else { unreachable!() };
+        assert!(diff(&test_context(&cli), args).await.is_ok());
+
+        let left = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nab").await;
+        let right = serve("HTTP/1.1 404 Not Found\r\nContent-Length: 2\r\n\r\nab").await;
+        let cli = Cli::try_parse_from(["httpie", "diff", &left, &right, "--diff-headers"]).unwrap();
+        let Command::Diff(ref args) = cli.command else { unreachable!() };
+        assert!(diff(&test_context(&cli), args).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn stream_body_works() {
+        let url = serve("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n").await;
+        let mut resp = Client::new().get(url).send().await.unwrap();
+        let mut out = Vec::new();
+        let progress = ProgressBar::hidden();
+        stream_body(&mut resp, &mut out, &progress, None, None).await.unwrap();
+        assert_eq!(out, b"abcde");
+        assert_eq!(progress.position(), 5);
+    }
+
+    #[tokio::test]
+    async fn chunked_download_works() {
+        let url = serve("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n").await;
+        let mut resp = Client::new().get(url).send().await.unwrap();
+        assert_eq!(resp.content_length(), None);
+        // Without a length to fill a bar with, progress is a spinner.
+        assert_eq!(download_progress(&resp).length(), None);
+        let path = std::env::temp_dir().join(format!("httpie-chunked-download-test-{}", std::process::id()));
+        let cli = Cli::try_parse_from(["httpie", "-q", "get", "http://a.b"]).unwrap();
+        assert_eq!(save_body(&mut resp, &cli, &path, false).await.unwrap(), 5);
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcde");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(format_meta(5, None, Version::HTTP_11, None), "5 bytes, no content type, HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn max_response_size_works() {
+        assert_eq!(capped(b"abcde", 0, None), (&b"abcde"[..], false));
+        assert_eq!(capped(b"abcde", 3, Some(5)), (&b"ab"[..], true));
+        assert_eq!(capped(b"ab", 3, Some(5)), (&b"ab"[..], false));
+
+        let chunked = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n";
+        let mut resp = Client::new().get(serve(chunked).await).send().await.unwrap();
+        let mut out = Vec::new();
+        let err = stream_body(&mut resp, &mut out, &ProgressBar::hidden(), Some(4), None).await.unwrap_err();
+        assert_eq!(out, b"abcd");
+        assert!(err.to_string().contains("--max-response-size 4 bytes"));
+
+        let mut resp = Client::new().get(serve(chunked).await).send().await.unwrap();
+        assert_eq!(read_body(&mut resp, Some(4)).await.unwrap(), (b"abcd".to_vec(), true));
+        let mut resp = Client::new().get(serve(chunked).await).send().await.unwrap();
+        assert_eq!(read_body(&mut resp, Some(6)).await.unwrap(), (b"abcdef".to_vec(), false));
+    }
+
+    #[tokio::test]
+    async fn limit_rate_works() {
+        assert_eq!(parse_rate("2048").unwrap(), 2048);
+        assert_eq!(parse_rate("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_rate("2M").unwrap(), 2 * 1024 * 1024);
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("5x").is_err());
+        assert!(parse_rate("k").is_err());
+
+        let started = Instant::now();
+        throttle(started, 100, None).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+        throttle(started, 100, Some(1000)).await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn redirect_policy_works() {
+        let target = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+        let redirect = format!("HTTP/1.1 302 Found\r\nLocation: {}end\r\nContent-Length: 0\r\n\r\n", target);
+        let start = serve(Box::leak(redirect.into_boxed_str())).await;
+        let client = Client::builder().redirect(redirect_policy(true)).build().unwrap();
+        let response = client.get(&start).send().await.unwrap();
+        assert_eq!(response.url().as_str(), format!("{}end", target));
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn stream_ndjson_works() {
+        colored::control::set_override(false);
+        let url = serve(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n\
+             c\r\n{\"a\":1}\n{\"b\"\r\n\
+             e\r\n:2}\r\nnot json\n\r\n\
+             5\r\n[1,2]\r\n0\r\n\r\n",
+        )
+        .await;
+        let mut resp = Client::new().get(url).send().await.unwrap();
+        assert!(is_ndjson(get_content_type(resp.headers()).as_ref()));
+        let mut out = Vec::new();
+        let format = JsonFormat {
+            compact: true,
+            ..Default::default()
+        };
+        assert_eq!(stream_ndjson(&mut resp, &mut out, format, None).await.unwrap(), 31);
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"a\":1}\n{\"b\":2}\nnot json\n[1,2]\n");
+    }
+
+    #[tokio::test]
+    async fn stream_events_works() {
+        colored::control::set_override(false);
+        let url = serve(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n\
+             14\r\n: hi\n\nevent: tick\nda\r\n\
+             1f\r\nta: 1\r\n\r\nid: 2\ndata: {\"a\":1}\n\n\r\n\
+             c\r\ndata: never\n\r\n0\r\n\r\n",
+        )
+        .await;
+        let mut resp = Client::new().get(url).send().await.unwrap();
+        assert!(is_event_stream(get_content_type(resp.headers()).as_ref()));
+        let mut out = Vec::new();
+        stream_events(&mut resp, &mut out, NonZeroUsize::new(2), None).await.unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), ": hi\nevent: tick\ndata: 1\n\nid: 2\ndata: {\"a\":1}\n\n");
+    }
+
+    #[tokio::test]
+    async fn download_path_works() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let resp = Client::new().get(format!("{}files/a.tar.gz", url)).send().await.unwrap();
+        assert_eq!(download_path(resp.url()), PathBuf::from("a.tar.gz"));
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        assert_eq!(download_path(Client::new().get(url).send().await.unwrap().url()), PathBuf::from("index"));
+    }
+
+    #[test]
+    fn format_headers_works() {
+        colored::control::set_override(false);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+        headers.append(header::SET_COOKIE, "a=1".parse().unwrap());
+        headers.append(header::SET_COOKIE, "b=2; Path=/; HttpOnly".parse().unwrap());
+        headers.insert(header::AGE, "5".parse().unwrap());
+        let sorted = HeaderDisplay {
+            sort: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_headers(&headers, sorted),
+            [
+                "         age: 5",
+                "content-type: text/plain",
+                "  set-cookie: a = 1",
+                "  set-cookie: b = 2",
+                "              Path: /",
+                "              HttpOnly",
+            ]
+        );
+        assert_eq!(format_headers(&headers, HeaderDisplay::default())[0], "content-type: text/plain");
+        let value = HeaderValue::from_bytes(b"caf\xe9 \"x\"").unwrap();
+        assert_eq!(format_header_value(&value), "caf\u{fffd} \"x\"");
+    }
+
+    #[test]
+    fn header_display_works() {
+        colored::control::set_override(false);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer s3cr3t".parse().unwrap());
+        headers.insert(header::SET_COOKIE, "session=abcdefgh; Path=/".parse().unwrap());
+        headers.insert("x-trace", "0123456789".parse().unwrap());
+        let cli = Cli::try_parse_from(["httpie", "--truncate-headers", "4", "--hide-header", "Authorization", "get", "http://a.b"]).unwrap();
+        assert_eq!(
+            format_headers(&headers, cli.header_display()),
+            [
+                "authorization: [redacted]",
+                "   set-cookie: sess…",
+                "               Path…",
+                "      x-trace: 0123…",
+            ]
+        );
+        assert_eq!(headers_json(&headers, cli.header_display())["authorization"], "[redacted]");
+    }
+
+    #[test]
+    fn format_cookie_works() {
+        colored::control::set_override(false);
+        let cookie = "session=a=b; Expires=Wed, 21 Oct 2026 07:28:00 GMT; Secure; SameSite = Lax";
+        assert_eq!(
+            format_cookie(&cookie.parse().unwrap()),
+            ["session = a=b", "Expires: Wed, 21 Oct 2026 07:28:00 GMT", "Secure", "SameSite: Lax"]
+        );
+        assert_eq!(format_cookie(&"".parse().unwrap()), [""]);
+    }
+
+    #[tokio::test]
+    async fn print_resp_json_works() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 8\r\n\r\n{\"a\": 1}").await;
+        let resp = Client::new().get(&url).send().await.unwrap();
+        assert_eq!(headers_json(resp.headers(), HeaderDisplay::default()), json!({"content-type": "application/json", "content-length": "8"}));
+        let expected = json!({
+            "status": 200,
+            "reason": "OK",
+            "version": "HTTP/1.1",
+            "headers": {"content-type": "application/json", "content-length": "8"},
+            "body": {"a": 1},
+        });
+        let cli = Cli::try_parse_from(["httpie", "--output-format", "json", "get", &url]).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(print_resp_json(resp, &cli, None, false, &mut out).await.unwrap(), b"{\"a\": 1}");
+        assert_eq!(serde_json::from_slice::<Value>(&out).unwrap(), expected);
+
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 8\r\n\r\n{\"a\": 1}").await;
+        let resp = Client::new().get(&url).send().await.unwrap();
+        let cli = Cli::try_parse_from(["httpie", "--output-format", "ndjson", "get", &url]).unwrap();
+        let mut out = Vec::new();
+        print_resp_json(resp, &cli, None, false, &mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.ends_with('\n'));
+        assert_eq!(serde_json::from_str::<Value>(&out).unwrap(), expected);
+
+        let mut headers = HeaderMap::new();
+        headers.append(header::SET_COOKIE, "a=1".parse().unwrap());
+        headers.append(header::SET_COOKIE, "b=2".parse().unwrap());
+        assert_eq!(headers_json(&headers, HeaderDisplay::default()), json!({"set-cookie": ["a=1", "b=2"]}));
+    }
+
+    #[test]
+    fn body_json_works() {
+        let filter = parse_filter(".a").unwrap();
+        let format = JsonFormat {
+            filter: Some(&filter),
+            ..Default::default()
+        };
+        assert_eq!(body_json(Some(&APPLICATION_JSON), r#"{"a": [1]}"#, format).unwrap(), json!([1]));
+        assert_eq!(body_json(None, "plain", JsonFormat::default()).unwrap(), json!("plain"));
+        assert!(body_json(None, "plain", format).is_err());
+    }
+
+    #[test]
+    fn clean_up_partial_files_works() {
+        let removed = std::env::temp_dir().join(format!("httpie-partial-removed-test-{}", std::process::id()));
+        let kept = std::env::temp_dir().join(format!("httpie-partial-kept-test-{}", std::process::id()));
+        std::fs::write(&removed, "part").unwrap();
+        std::fs::write(&kept, "part").unwrap();
+        let registered = |path: &Path| PARTIAL_FILES.lock().unwrap().iter().any(|(p, _)| p == path);
+        let partial = PartialFile::register(&removed, false);
+        assert!(registered(&removed));
+        drop(partial);
+        assert!(!registered(&removed));
+
+        clean_up(vec![(removed.clone(), false), (kept.clone(), true)]);
+        assert!(!removed.exists());
+        assert!(kept.exists());
+        std::fs::remove_file(&kept).unwrap();
+    }
+
+    #[test]
+    fn get_content_type_works() {
+        let content_type = |value: &[u8]| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_bytes(value).unwrap());
+            get_content_type(&headers)
+        };
+        assert_eq!(content_type(b"text/plain").unwrap(), mime::TEXT_PLAIN);
+        assert_eq!(content_type(b"not a mime"), None);
+        assert_eq!(content_type(b"text/\xff"), None);
+        assert_eq!(get_content_type(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn print_encoded_body_works() {
+        let cli = Cli::try_parse_from(["httpie", "--meta", "get", "http://a.b"]).unwrap();
+        let raw = encoding::encode("br", b"hello, hello, hello").unwrap();
+        assert_ne!(raw.len(), 19);
+        let (size, body) = print_encoded_body(&raw, "br", Some(mime::TEXT_PLAIN), &cli).unwrap();
+        assert_eq!((size, body.unwrap()), (19, b"hello, hello, hello".to_vec()));
+        assert_eq!(print_encoded_body(b"nope", "br", None, &cli).unwrap(), (4, None));
+    }
+
+    #[test]
+    fn format_meta_works() {
+        let mime: Mime = "application/json; charset=utf-8".parse().unwrap();
+        assert_eq!(format_meta(42, Some(&mime), Version::HTTP_11, None), "42 bytes, application/json, HTTP/1.1");
+        assert_eq!(format_meta(0, None, Version::HTTP_2, None), "0 bytes, no content type, HTTP/2.0");
+        assert_eq!(format_meta(9, None, Version::HTTP_11, Some("br")), "9 bytes, no content type, HTTP/1.1, br-encoded");
+    }
+
+    #[tokio::test]
+    async fn quiet_works() {
+        let path = std::env::temp_dir().join(format!("httpie-quiet-test-{}", std::process::id()));
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await;
+        let cli = Cli::try_parse_from(["httpie", "-q", "-o", path.to_str().unwrap(), "get", &url]).unwrap();
+        assert_eq!(cli.print_flags(), PrintFlags::default());
+        print_resp(Client::new().get(&url).send().await.unwrap(), &cli, None).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"ok");
+        std::fs::remove_file(&path).unwrap();
+        assert!(Cli::try_parse_from(["httpie", "-q", "-v", "get", &url]).is_err());
+    }
+
+    #[test]
+    fn verbose_works() {
+        let cli = Cli::try_parse_from(["httpie", "-v", "get", "http://a.b"]).unwrap();
+        assert_eq!(cli.print_flags(), parse_print_flags("HBhb").unwrap());
+        assert!(Cli::try_parse_from(["httpie", "-v", "--print", "b", "get", "http://a.b"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn http2_rejection_is_explained() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let cli = Cli::try_parse_from(["httpie", "--http2", "get", &url]).unwrap();
+        let ctx = Context {
+            client: Client::builder().http2_prior_knowledge().build().unwrap(),
+            ..test_context(&cli)
+        };
+        let err = send(&ctx, ctx.client.get(&url)).await.unwrap_err();
+        assert!(err.to_string().contains("HTTP/2"));
+        assert!(Cli::try_parse_from(["httpie", "--http1", "--http2", "get", &url]).is_err());
+    }
+
+    #[test]
+    fn parse_print_flags_works() {
+        assert!(parse_print_flags("hx").is_err());
+        assert_eq!(parse_print_flags("").unwrap(), PrintFlags::default());
+        assert_eq!(
+            parse_print_flags("Hb").unwrap(),
+            PrintFlags {
+                request_headers: true,
+                request_body: false,
+                response_headers: false,
+                response_body: true,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_filter_works() {
+        assert!(parse_filter("a..b").is_err());
+        assert!(parse_filter(".items[x]").is_err());
+        assert_eq!(parse_filter(".").unwrap().segments, vec![]);
+        assert_eq!(parse_filter(".[1]").unwrap().segments, vec![Segment::Index(1)]);
+        assert_eq!(
+            parse_filter(".data.items[0].id").unwrap().segments,
+            vec![
+                Segment::Key("data".into()),
+                Segment::Key("items".into()),
+                Segment::Index(0),
+                Segment::Key("id".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_apply_works() {
+        let value: Value = serde_json::from_str(r#"{"data": {"items": [{"id": 7}]}}"#).unwrap();
+        assert_eq!(parse_filter(".data.items[0].id").unwrap().apply(&value).unwrap(), 7);
+        assert!(parse_filter(".data.items[1]").unwrap().apply(&value).is_err());
+        let filter = parse_filter(".a").unwrap();
+        let format = JsonFormat {
+            filter: Some(&filter),
+            ..Default::default()
+        };
+        assert!(print_body(None, "plain", format, None).is_err());
+    }
+
+    #[test]
+    fn select_pointer_works() {
+        assert!(parse_pointer("data/items").is_err());
+        let json = Some(APPLICATION_JSON);
+        let body = r#"{"data": {"items": [{"name": "a"}]}}"#.to_string();
+        let format = |pointer| JsonFormat {
+            pointer: Some(pointer),
+            ..Default::default()
+        };
+        assert_eq!(render_json(&body, format("/data/items/0/name")).unwrap(), "\"a\"");
+        assert!(print_body(json, &body, format("/data/items/1"), None).is_err());
+    }
+
+    #[test]
+    fn top_level_json_works() {
+        let format = JsonFormat::default();
+        assert_eq!(render_json("[1,{\"a\":null}]", format).unwrap(), "[\n  1,\n  {\n    \"a\": null\n  }\n]");
+        assert_eq!(render_json("\"x\"", format).unwrap(), "\"x\"");
+        assert_eq!(render_json("42", format).unwrap(), "42");
+        colored::control::set_override(false);
+        assert!(print_body(Some(APPLICATION_JSON), "not json", format, None).is_ok());
+        assert!(print_body(Some(APPLICATION_JSON), "{\"a\": [", format, None).is_ok());
+    }
+
+    #[test]
+    fn pretty_works() {
+        let body = "{\"a\":[1]}";
+        let cli = Cli::try_parse_from(["httpie", "--pretty", "none", "get", "http://a.b"]).unwrap();
+        assert_eq!(render_json(body, cli.json_format()).unwrap(), body);
+        let cli = Cli::try_parse_from(["httpie", "--pretty", "format", "get", "http://a.b"]).unwrap();
+        assert_eq!(render_json(body, cli.json_format()).unwrap(), "{\n  \"a\": [\n    1\n  ]\n}");
+        let cli = Cli::try_parse_from(["httpie", "--pretty", "colors", "--select", "/a", "get", "http://a.b"]).unwrap();
+        assert_eq!(render_json(body, cli.json_format()).unwrap(), "[\n  1\n]");
+        assert!(Cli::try_parse_from(["httpie", "--pretty", "all", "--color", "never", "get", "http://a.b"]).is_err());
+    }
+
+    #[test]
+    fn compact_json_works() {
+        let format = JsonFormat {
+            compact: true,
+            ..Default::default()
+        };
+        assert_eq!(render_json("{\n  \"b\": [1, 2],\n  \"a\": \"x y\"\n}", format).unwrap(), r#"{"b":[1,2],"a":"x y"}"#);
+        let format = JsonFormat {
+            sorted: true,
+            ..format
+        };
+        assert_eq!(render_json(r#"{"b": 1, "a": 2}"#, format).unwrap(), r#"{"a":2,"b":1}"#);
+    }
+
+    #[test]
+    fn grep_works() {
+        assert!(parse_grep("(").is_err());
+        let pattern = parse_grep("id|name").unwrap();
+        let text = "{\n  \"id\": 1,\n  \"tag\": \"x\",\n  \"name\": \"a\"\n}";
+        let grep = Grep { pattern: &pattern, invert: false };
+        colored::control::set_override(false);
+        assert_eq!(grep.lines(text, |s| format!("<{}>", s)), ["<  \">id<\": 1,>", "<  \">name<\": \"a\">"]);
+        let grep = Grep { invert: true, ..grep };
+        assert_eq!(grep.lines(text, |s| s.to_string()), ["{", "  \"tag\": \"x\",", "}"]);
+        assert!(Cli::try_parse_from(["httpie", "--grep-invert", "get", "http://a.b"]).is_err());
+    }
+
+    #[test]
+    fn sorted_json_works() {
+        let format = JsonFormat {
+            sorted: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_json(r#"{"b": 1, "a": {"d": 2, "c": 3}}"#, format).unwrap(),
+            "{\n  \"a\": {\n    \"c\": 3,\n    \"d\": 2\n  },\n  \"b\": 1\n}"
+        );
+        assert!(render_json("nope", format).is_err());
+    }
+}
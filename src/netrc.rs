@@ -0,0 +1,145 @@
+//! Reading credentials from a `.netrc` file for `--netrc` and `--netrc-file`.
+
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Entry {
+    login: Option<String>,
+    password: Option<String>,
+}
+
+/// The `machine` entries of a netrc file and its `default` one, if any.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Netrc {
+    machines: Vec<(String, Entry)>,
+    default: Option<Entry>,
+}
+
+impl Netrc {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut netrc = Self::default();
+        let mut tokens = Tokens::new(content);
+        // Where `login`/`password` go: the last `machine` or the `default` entry.
+        let mut in_default = false;
+        while let Some(token) = tokens.next() {
+            let mut value = |name: &str| tokens.next().ok_or_else(|| anyhow!("{} is missing its value", name));
+            match token {
+                "machine" => {
+                    let host = value("machine")?;
+                    netrc.machines.push((host.to_string(), Entry::default()));
+                    in_default = false;
+                }
+                "default" => {
+                    netrc.default = Some(Entry::default());
+                    in_default = true;
+                }
+                "login" | "password" | "account" => {
+                    let v = value(token)?.to_string();
+                    let entry = if in_default {
+                        netrc.default.as_mut()
+                    } else {
+                        netrc.machines.last_mut().map(|(_, entry)| entry)
+                    };
+                    let entry = entry.ok_or_else(|| anyhow!("{} appears before any machine", token))?;
+                    match token {
+                        "login" => entry.login = Some(v),
+                        "password" => entry.password = Some(v),
+                        _ => {}
+                    }
+                }
+                "macdef" => {
+                    value("macdef")?;
+                    tokens.skip_macro();
+                }
+                other => return Err(anyhow!("Unexpected token {}", other)),
+            }
+        }
+        Ok(netrc)
+    }
+
+    /// Returns `(login, password)` for `host`, falling back to the `default` entry.
+    pub fn credentials(&self, host: &str) -> Option<(String, String)> {
+        let entry = self
+            .machines
+            .iter()
+            .find(|(machine, _)| machine.eq_ignore_ascii_case(host))
+            .map(|(_, entry)| entry)
+            .or(self.default.as_ref())?;
+        Some((entry.login.clone()?, entry.password.clone().unwrap_or_default()))
+    }
+}
+
+/// Whitespace-separated tokens, tracking the rest of the input so a macro body can be skipped.
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(content: &'a str) -> Self {
+        Self { rest: content }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let start = self.rest.trim_start();
+        if start.is_empty() {
+            return None;
+        }
+        let end = start.find(char::is_whitespace).unwrap_or(start.len());
+        self.rest = &start[end..];
+        Some(&start[..end])
+    }
+
+    /// A macro body runs from the next line up to the first empty line.
+    fn skip_macro(&mut self) {
+        self.rest = match self.rest.find("\n\n") {
+            Some(end) => &self.rest[end + 2..],
+            None => "",
+        };
+    }
+}
+
+/// `~/.netrc`, used by `--netrc`.
+pub fn default_path() -> Option<PathBuf> {
+    Some(PathBuf::from(std::env::var_os("HOME")?).join(".netrc"))
+}
+
+/// Reads and parses the file, warning when other users can read it.
+pub fn load(path: &Path) -> Result<Netrc> {
+    let content = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read netrc {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if std::fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+            use colored::Colorize;
+            eprintln!(
+                "{} {} is readable by other users; consider chmod 600",
+                "warning:".yellow(),
+                path.display()
+            );
+        }
+    }
+    Netrc::parse(&content).map_err(|e| anyhow!("Failed to parse netrc {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_netrc_works() {
+        let netrc = Netrc::parse(
+            "machine api.example.com login alice password s3cr3t\n\
+             macdef init\ncd /pub\nmachine evil.com login x\n\n\
+             machine nopass.example.com\n  login bob\n\
+             default login anon password guest\n",
+        )
+        .unwrap();
+        assert_eq!(netrc.credentials("API.example.com"), Some(("alice".into(), "s3cr3t".into())));
+        assert_eq!(netrc.credentials("nopass.example.com"), Some(("bob".into(), "".into())));
+        assert_eq!(netrc.credentials("evil.com"), Some(("anon".into(), "guest".into())));
+        assert_eq!(Netrc::parse("machine a.b").unwrap().credentials("other"), None);
+        assert!(Netrc::parse("login alice").is_err());
+        assert!(Netrc::parse("machine").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+//! Fetching a bearer token with the OAuth2 client-credentials grant for `--oauth2-token-url`.
+
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How long before it expires a token stops being reused, so it doesn't run out mid-request.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// A token, with what it was issued for so a session only reuses it for the same request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Token {
+    pub url: String,
+    pub client_id: String,
+    pub scope: Option<String>,
+    pub access_token: String,
+    /// Seconds since the epoch when it expires, if the server said.
+    pub expires_at: Option<u64>,
+}
+
+impl Token {
+    /// Whether the token was issued for this endpoint, client and scope and hasn't expired;
+    /// one without an expiry is never reused.
+    pub fn is_valid_for(&self, url: &str, client_id: &str, scope: Option<&str>, now: SystemTime) -> bool {
+        let Some(expires_at) = self.expires_at else {
+            return false;
+        };
+        let now = (now + EXPIRY_MARGIN).duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::ZERO);
+        self.url == url && self.client_id == client_id && self.scope.as_deref() == scope && now.as_secs() < expires_at
+    }
+}
+
+/// Asks the token endpoint for a token, authenticating the client with Basic auth as RFC 6749
+/// recommends.
+pub async fn fetch_token(client: &Client, url: &str, id: &str, secret: &str, scope: Option<&str>) -> Result<Token> {
+    let mut form = vec![("grant_type", "client_credentials")];
+    if let Some(scope) = scope {
+        form.push(("scope", scope));
+    }
+    let requested = SystemTime::now();
+    let response = client
+        .post(url)
+        .basic_auth(id, Some(secret))
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to fetch an OAuth2 token from {}: {}", url, e))?;
+    let status = response.status();
+    let body = response.text().await?;
+    let failed = |e| anyhow!("Failed to get an OAuth2 token from {} ({}): {}", url, status, e);
+    let value: Value = serde_json::from_str(&body).map_err(|_| failed(anyhow!("the response isn't JSON")))?;
+    let access_token = access_token(&value).map_err(failed)?;
+    // Counting from when it was asked for errs on the side of expiring early.
+    let expires_at = value
+        .get("expires_in")
+        .and_then(Value::as_u64)
+        .and_then(|secs| (requested + Duration::from_secs(secs)).duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|at| at.as_secs());
+    Ok(Token { url: url.to_string(), client_id: id.to_string(), scope: scope.map(str::to_string), access_token, expires_at })
+}
+
+/// The `access_token` of a token response, or the error the server gave instead.
+fn access_token(value: &Value) -> Result<String> {
+    if let Some(token) = value.get("access_token").and_then(Value::as_str) {
+        return Ok(token.to_string());
+    }
+    match (value.get("error").and_then(Value::as_str), value.get("error_description").and_then(Value::as_str)) {
+        (Some(error), Some(description)) => Err(anyhow!("{}: {}", error, description)),
+        (Some(error), None) => Err(anyhow!("{}", error)),
+        _ => Err(anyhow!("the response has no access_token")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn access_token_works() {
+        assert_eq!(access_token(&json!({"access_token": "t0k", "token_type": "Bearer"})).unwrap(), "t0k");
+        let err = access_token(&json!({"error": "invalid_client", "error_description": "bad secret"})).unwrap_err();
+        assert_eq!(err.to_string(), "invalid_client: bad secret");
+        assert!(access_token(&json!({})).is_err());
+    }
+
+    #[test]
+    fn is_valid_for_works() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let token = Token {
+            url: "https://a.b/token".into(),
+            client_id: "app".into(),
+            scope: None,
+            access_token: "t0k".into(),
+            expires_at: Some(1100),
+        };
+        assert!(token.is_valid_for("https://a.b/token", "app", None, now));
+        assert!(!token.is_valid_for("https://a.b/token", "app", None, now + Duration::from_secs(80)));
+        assert!(!token.is_valid_for("https://a.b/token", "other", None, now));
+        assert!(!token.is_valid_for("https://a.b/token", "app", Some("read"), now));
+        assert!(!Token { expires_at: None, ..token }.is_valid_for("https://a.b/token", "app", None, now));
+    }
+
+    #[tokio::test]
+    async fn fetch_token_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut wire = Vec::new();
+            let mut buf = [0; 4096];
+            while !wire.ends_with(b"grant_type=client_credentials&scope=read") {
+                let n = socket.read(&mut buf).await.unwrap();
+                wire.extend_from_slice(&buf[..n]);
+            }
+            let wire = String::from_utf8(wire).unwrap();
+            // base64 of `app:s3cr3t`.
+            assert!(wire.contains("authorization: Basic YXBwOnMzY3IzdA==\r\n"));
+            let body = r#"{"access_token":"t0k","expires_in":3600}"#;
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        let token = fetch_token(&Client::new(), &url, "app", "s3cr3t", Some("read")).await.unwrap();
+        assert_eq!(token.access_token, "t0k");
+        assert!(token.is_valid_for(&url, "app", Some("read"), SystemTime::now() + Duration::from_secs(3000)));
+    }
+}
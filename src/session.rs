@@ -0,0 +1,153 @@
+//! Sessions for `--session`: headers given with `-H` and cookies the server set, kept in a
+//! JSON file and sent again with later requests to the same host, along with any OAuth2 token
+//! until it expires.
+
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use crate::oauth2;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub headers: BTreeMap<String, String>,
+    pub cookies: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth2_token: Option<oauth2::Token>,
+}
+
+/// A name with a `/` or ending in `.json` is a path; a bare name is kept per host under the
+/// config directory, like `~/.config/httpie/sessions/example.com/dev.json`.
+pub fn path(name: &str, host: &str) -> Option<PathBuf> {
+    if name.contains('/') || name.ends_with(".json") {
+        return Some(PathBuf::from(name));
+    }
+    let dirs = directories::ProjectDirs::from("", "", "httpie")?;
+    Some(dirs.config_dir().join("sessions").join(host).join(format!("{}.json", name)))
+}
+
+/// The session stored at `path`, or an empty one when there's none yet.
+pub fn load(path: &Path) -> Result<Session> {
+    match std::fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content).map_err(|e| anyhow!("Failed to parse session {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Session::default()),
+        Err(e) => Err(anyhow!("Failed to read session {}: {}", path.display(), e)),
+    }
+}
+
+/// Writes the session, creating the file readable only by its owner as it holds credentials.
+pub fn save(path: &Path, session: &Session) -> Result<()> {
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(&serde_json::to_vec_pretty(session)?)
+    };
+    write().map_err(|e| anyhow!("Failed to write session {}: {}", path.display(), e))
+}
+
+impl Session {
+    /// Adds the stored headers and cookies to `headers`, leaving any it already has.
+    pub fn apply(&self, headers: &mut HeaderMap) -> Result<()> {
+        for (name, value) in &self.headers {
+            let name: header::HeaderName = name.parse()?;
+            if !headers.contains_key(&name) {
+                headers.insert(name, value.parse()?);
+            }
+        }
+        if !self.cookies.is_empty() && !headers.contains_key(header::COOKIE) {
+            let cookies: Vec<_> = self.cookies.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            headers.insert(header::COOKIE, cookies.join("; ").parse()?);
+        }
+        Ok(())
+    }
+
+    /// Keeps the `-H` headers, except those that only describe one request, and the cookies
+    /// the response set or removed.
+    pub fn update(&mut self, overrides: &HeaderMap, response: &HeaderMap) {
+        for (name, value) in overrides {
+            let name = name.as_str();
+            if name.starts_with("content-") || name.starts_with("if-") || name == "cookie" {
+                continue;
+            }
+            if let Ok(value) = value.to_str() {
+                self.headers.insert(name.to_string(), value.to_string());
+            }
+        }
+        for value in response.get_all(header::SET_COOKIE) {
+            if let Some((name, value, removed)) = parse_set_cookie(value) {
+                match removed {
+                    true => self.cookies.remove(&name),
+                    false => self.cookies.insert(name, value),
+                };
+            }
+        }
+    }
+}
+
+/// The name and value of a `Set-Cookie`, and whether it deletes the cookie with `Max-Age=0`.
+fn parse_set_cookie(value: &HeaderValue) -> Option<(String, String, bool)> {
+    let value = value.to_str().ok()?;
+    let mut parts = value.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let removed = parts.any(|part| {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        key.trim().eq_ignore_ascii_case("max-age") && value.trim().parse::<i64>().is_ok_and(|age| age <= 0)
+    });
+    Some((name.trim().to_string(), value.trim().to_string(), removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_and_apply_work() {
+        let mut overrides = HeaderMap::new();
+        overrides.insert("x-team", HeaderValue::from_static("api"));
+        overrides.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        let mut response = HeaderMap::new();
+        response.append(header::SET_COOKIE, HeaderValue::from_static("sid=abc; Path=/; HttpOnly"));
+        response.append(header::SET_COOKIE, HeaderValue::from_static("theme=dark"));
+        let mut session = Session::default();
+        session.update(&overrides, &response);
+        assert_eq!(session.headers.keys().collect::<Vec<_>>(), ["x-team"]);
+
+        let mut removal = HeaderMap::new();
+        removal.insert(header::SET_COOKIE, HeaderValue::from_static("theme=; Max-Age=0"));
+        session.update(&HeaderMap::new(), &removal);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-team", HeaderValue::from_static("cli"));
+        session.apply(&mut headers).unwrap();
+        assert_eq!(headers["x-team"], "cli");
+        assert_eq!(headers[header::COOKIE], "sid=abc");
+    }
+
+    #[test]
+    fn load_and_save_work() {
+        let path = std::env::temp_dir().join(format!("httpie-session-test-{}", std::process::id())).join("s.json");
+        assert_eq!(load(&path).unwrap(), Session::default());
+        let session = Session { cookies: BTreeMap::from([("sid".into(), "abc".into())]), ..Default::default() };
+        save(&path, &session).unwrap();
+        assert_eq!(load(&path).unwrap(), session);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(super::path("dev", "a.b").unwrap().file_name().unwrap(), "dev.json");
+        assert_eq!(super::path("./s.json", "a.b").unwrap(), PathBuf::from("./s.json"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Sending requests over a Unix domain socket instead of TCP.
+//!
+//! reqwest can't connect to a socket, so the prepared request is replayed through a hyper
+//! client whose connector always dials the socket. Only plain HTTP/1 is spoken: TLS, proxies,
+//! redirects and `--timeout` don't apply in this mode.
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use anyhow::{anyhow, Result};
+use hyper::client::connect::{Connected, Connection};
+use hyper::service::Service;
+use hyper::{Body, Uri};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UnixStream;
+
+/// A socket stream that hyper can use as a connection.
+struct UnixConnection(UnixStream);
+
+impl Connection for UnixConnection {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for UnixConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// Connects to the same socket whatever host the request names.
+#[derive(Clone)]
+struct UnixConnector(PathBuf);
+
+impl Service<Uri> for UnixConnector {
+    type Response = UnixConnection;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<UnixConnection>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Uri) -> Self::Future {
+        let path = self.0.clone();
+        Box::pin(async move { UnixStream::connect(path).await.map(UnixConnection) })
+    }
+}
+
+/// Sends `request` over the socket at `path`, returning the response as a reqwest one so it
+/// prints like any other.
+pub async fn execute(path: &Path, request: Request) -> Result<Response> {
+    let url = request.url().clone();
+    let body = match request.body() {
+        Some(body) => body
+            .as_bytes()
+            .ok_or_else(|| anyhow!("Streaming bodies can't be sent over --unix-socket"))?
+            .to_vec(),
+        None => Vec::new(),
+    };
+    let mut builder = http::Request::builder()
+        .method(request.method().clone())
+        .uri(url.as_str());
+    *builder.headers_mut().unwrap() = request.headers().clone();
+    let request = builder.body(Body::from(body))?;
+
+    let client = hyper::Client::builder().build::<_, Body>(UnixConnector(path.to_path_buf()));
+    let response = client
+        .request(request)
+        .await
+        .map_err(|e| anyhow!("Request over {} failed: {}", path.display(), e))?;
+
+    let (parts, body) = response.into_parts();
+    let mut builder = http::Response::builder()
+        .status(parts.status)
+        .version(parts.version)
+        .url(url);
+    *builder.headers_mut().unwrap() = parts.headers;
+    Ok(builder.body(body)?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn execute_works() {
+        let path = std::env::temp_dir().join(format!("httpie-unix-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(buf[..n].starts_with(b"GET /v1/status HTTP/1.1\r\n"));
+            socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await.unwrap();
+        });
+
+        let request = reqwest::Client::new().get("http://localhost/v1/status").build().unwrap();
+        let response = execute(&path, request).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.url().path(), "/v1/status");
+        assert_eq!(response.text().await.unwrap(), "ok");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
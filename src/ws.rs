@@ -0,0 +1,76 @@
+//! Talking to a WebSocket endpoint for the `ws` subcommand.
+
+use anyhow::{anyhow, Result};
+use reqwest::{Request, Response};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+pub use tokio_tungstenite::tungstenite::Message;
+
+pub type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Headers the handshake sets itself, which mustn't be copied from the request.
+const HANDSHAKE_HEADERS: [&str; 6] = [
+    "connection",
+    "upgrade",
+    "host",
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "content-length",
+];
+
+/// Opens the connection with a handshake carrying the headers of `request`, such as its auth,
+/// returning the socket and the server's handshake response.
+pub async fn connect(request: &Request) -> Result<(Socket, Response)> {
+    let url = request.url();
+    if !matches!(url.scheme(), "ws" | "wss") {
+        return Err(anyhow!("WebSocket URLs start with ws:// or wss://, not {}://", url.scheme()));
+    }
+    let mut handshake = url.as_str().into_client_request()?;
+    for (name, value) in request.headers() {
+        if !HANDSHAKE_HEADERS.contains(&name.as_str()) {
+            handshake.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    let (socket, response) = tokio_tungstenite::connect_async(handshake)
+        .await
+        .map_err(|e| anyhow!("WebSocket handshake with {} failed: {}", url, e))?;
+    Ok((socket, response.map(Option::unwrap_or_default).into()))
+}
+
+/// How a received frame is shown: text as it is, anything else as a note. Control frames the
+/// connection answers by itself are left out.
+pub fn describe(message: &Message) -> Option<String> {
+    match message {
+        Message::Text(text) => Some(text.clone()),
+        Message::Binary(data) => Some(format!("[binary frame, {} bytes]", data.len())),
+        Message::Close(Some(frame)) if frame.reason.is_empty() => Some(format!("[closed: {}]", u16::from(frame.code))),
+        Message::Close(Some(frame)) => Some(format!("[closed: {} {}]", u16::from(frame.code), frame.reason)),
+        Message::Close(None) => Some("[closed]".to_string()),
+        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+    #[test]
+    fn describe_works() {
+        assert_eq!(describe(&Message::Text("hi".into())).unwrap(), "hi");
+        assert_eq!(describe(&Message::Binary(vec![0; 3])).unwrap(), "[binary frame, 3 bytes]");
+        let close = CloseFrame { code: CloseCode::Normal, reason: "bye".into() };
+        assert_eq!(describe(&Message::Close(Some(close))).unwrap(), "[closed: 1000 bye]");
+        assert_eq!(describe(&Message::Ping(Vec::new())), None);
+    }
+
+    #[tokio::test]
+    async fn connect_rejects_http_urls() {
+        let request = reqwest::Client::new().get("http://a.b/").build().unwrap();
+        let err = connect(&request).await.unwrap_err();
+        assert_eq!(err.to_string(), "WebSocket URLs start with ws:// or wss://, not http://");
+    }
+}
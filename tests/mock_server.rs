@@ -0,0 +1,103 @@
+//! Runs the built binary against a mock server, checking what goes over the wire.
+
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use serde_json::json;
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::process::Command;
+use wiremock::matchers::{body_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Runs `httpie` with `args`, away from any config or stdin of the user's.
+async fn httpie(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_httpie"))
+        .args(["--color", "never"])
+        .args(args)
+        .env("XDG_CONFIG_HOME", std::env::temp_dir().join("httpie-mock-server-no-config"))
+        .env_remove("HTTPIE_CONFIG")
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn get_sends_default_headers() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/status"))
+        .and(header("user-agent", "Rust Httpie"))
+        .and(header("accept", "*/*"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ok": true})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let output = httpie(&["--print", "b", "get", &format!("{}/status", server.uri())]).await;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "{\n  \"ok\": true\n}");
+}
+
+#[tokio::test]
+async fn post_sends_json_body() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/users"))
+        .and(header("content-type", "application/json"))
+        .and(body_json(json!({"name": "alice", "age": 30, "tags": ["a"]})))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let url = format!("{}/users", server.uri());
+    let output = httpie(&["-q", "post", &url, "name=alice", "age:=30", "tags:=[\"a\"]"]).await;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[tokio::test]
+async fn expect_status_fails_on_other_statuses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    let url = format!("{}/missing", server.uri());
+    assert!(httpie(&["-q", "get", &url]).await.status.success());
+
+    let output = httpie(&["-q", "--expect-status", "2xx", "get", &url]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Expected status 2xx, got 404 Not Found"));
+}
+
+#[tokio::test]
+async fn connect_timeout_names_its_limit() {
+    // A listener that never accepts, with its queue already full, leaves new connections hanging.
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut queued = Vec::new();
+    while let Ok(Ok(stream)) = tokio::time::timeout(Duration::from_millis(100), TcpStream::connect(addr)).await {
+        queued.push(stream);
+    }
+    let output = httpie(&["--connect-timeout", "0.2", "get", &format!("http://{}/", addr)]).await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("could not connect to {} within the 200ms --connect-timeout", addr)), "{}", stderr);
+    assert_eq!(output.status.code(), Some(28));
+}
+
+#[tokio::test]
+async fn show_url_prints_the_final_url() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/users/a%20b"))
+        .and(query_param("q", "x y"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let url = format!("{}/users/{{id}}?q=x y&tag=1", server.uri());
+    let output = httpie(&["-q", "--show-url", "--path-var", "id=a b", "get", &url]).await;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let expected = format!("url: {}/users/a%20b?q=x%20y&tag=1", server.uri());
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), expected);
+}